@@ -0,0 +1,123 @@
+//! Compares the byte string escaping used by `Segment::Bytes` against a naive byte at a time
+//! implementation, for blobs of different sizes and densities of null bytes.
+
+#[macro_use]
+extern crate criterion;
+extern crate binary_tuples;
+
+use binary_tuples::{Tuple, TupleError};
+use binary_tuples::segment::Segment;
+use criterion::{black_box, BenchmarkId, Criterion, Throughput};
+
+const SIZES: [usize; 3] = [64, 4096, 1 << 20];
+
+/// Generate a blob where roughly one in every `spacing` bytes is null
+fn blob(size: usize, spacing: usize) -> Vec<u8> {
+    (0..size)
+        .map(|i| if spacing > 0 && i % spacing == 0 { 0 } else { (i % 255) as u8 + 1 })
+        .collect()
+}
+
+fn naive_encode(input: &[u8], buffer: &mut Vec<u8>) {
+    buffer.reserve(input.len() + 2);
+    buffer.push(0x01);
+
+    for value in input.iter() {
+        match *value {
+            0x00 => {
+                buffer.push(0x00);
+                buffer.push(0xFF);
+            }
+            value => buffer.push(value)
+        }
+    }
+
+    buffer.push(0x00);
+}
+
+fn naive_decode(input: &[u8]) -> Option<(usize, Vec<u8>)> {
+    let mut read = 0;
+    let mut skip = false;
+    let mut bytes = Vec::with_capacity(1024);
+    for vals in input.windows(2) {
+        if skip {
+            skip = false;
+            read += 1;
+        } else if vals[0] == 0 {
+            read += 1;
+            if vals[1] != 0xFF {
+                return Some((read, bytes))
+            } else {
+                bytes.push(vals[0]);
+                skip = true;
+            }
+        } else {
+            read += 1;
+            bytes.push(vals[0])
+        }
+    }
+
+    // A terminator in the last byte is never the start of a window
+    if !skip && input.last() == Some(&0x00) {
+        Some((read + 1, bytes))
+    } else {
+        None
+    }
+}
+
+/// Decode a tuple holding a single byte string with the naive unescaping, doing the same checks as
+/// `Tuple::as_segments` so that the two only differ in how the byte string is decoded
+fn naive_as_segments(input: &[u8]) -> Result<Vec<Segment>, TupleError> {
+    if input.first() != Some(&0x01) {
+        return Err(TupleError::DecodeError { position: 0, type_code: input.first().cloned().unwrap_or(0) });
+    }
+
+    match naive_decode(&input[1..]) {
+        Some((read, bytes)) if read + 1 == input.len() => Ok(vec![Segment::Bytes(bytes)]),
+        _ => Err(TupleError::TruncatedTuple),
+    }
+}
+
+fn bench_escaping(c: &mut Criterion) {
+    for &(name, spacing) in [("no_nulls", 0), ("sparse_nulls", 64), ("dense_nulls", 2)].iter() {
+        let mut group = c.benchmark_group(format!("encode_bytes/{}", name));
+        for &size in SIZES.iter() {
+            let input = blob(size, spacing);
+            let segment = Segment::Bytes(input.clone());
+            group.throughput(Throughput::Bytes(size as u64));
+
+            group.bench_with_input(BenchmarkId::new("memchr", size), &segment, |b, segment| {
+                b.iter(|| {
+                    let mut buffer = Vec::new();
+                    segment.encode(&mut buffer);
+                    black_box(buffer)
+                })
+            });
+            group.bench_with_input(BenchmarkId::new("naive", size), &input, |b, input| {
+                b.iter(|| {
+                    let mut buffer = Vec::new();
+                    naive_encode(input, &mut buffer);
+                    black_box(buffer)
+                })
+            });
+        }
+        group.finish();
+
+        let mut group = c.benchmark_group(format!("decode_bytes/{}", name));
+        for &size in SIZES.iter() {
+            let tuple = Tuple::from_segments(&[Segment::Bytes(blob(size, spacing))]);
+            group.throughput(Throughput::Bytes(size as u64));
+
+            group.bench_with_input(BenchmarkId::new("memchr", size), &tuple, |b, tuple| {
+                b.iter(|| black_box(tuple.as_segments().unwrap()))
+            });
+            group.bench_with_input(BenchmarkId::new("naive", size), &tuple, |b, tuple| {
+                b.iter(|| black_box(naive_as_segments(tuple.as_bytes()).unwrap()))
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_escaping);
+criterion_main!(benches);
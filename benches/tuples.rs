@@ -0,0 +1,131 @@
+//! Benchmarks for building, encoding and decoding tuples
+
+#[macro_use]
+extern crate criterion;
+#[macro_use]
+extern crate binary_tuples;
+
+use binary_tuples::{AddToTuple, ArrayTuple, Tuple, TupleIndex};
+use binary_tuples::segment::Segment;
+use criterion::{black_box, BatchSize, BenchmarkId, Criterion};
+
+fn segments() -> Vec<(&'static str, Segment)> {
+    vec![
+        ("bytes", Segment::Bytes(vec![1, 2, 3, 4, 5, 6, 7, 8])),
+        ("string", Segment::String(String::from("posts"))),
+        ("const", Segment::Const("users")),
+        ("integer_small", Segment::Integer(42)),
+        ("integer_large", Segment::Integer(i64::MAX)),
+        ("integer_negative", Segment::Integer(-5000)),
+        ("float", Segment::Float(1.5)),
+        ("double", Segment::Double(31415.514)),
+        ("boolean", Segment::Boolean(true)),
+        ("uuid", Segment::UUID([7; 16])),
+        ("nested", Segment::Nested(vec![Segment::Const("tags"), Segment::Integer(3)])),
+    ]
+}
+
+fn nested(depth: usize) -> Segment {
+    (0..depth).fold(Segment::Integer(1), |inner, _| Segment::Nested(vec![inner]))
+}
+
+fn bench_construction(c: &mut Criterion) {
+    let mut group = c.benchmark_group("construct");
+
+    group.bench_function("tuple_macro", |b| {
+        b.iter(|| black_box(tuple!("users", black_box(1234), "posts", black_box(98765))))
+    });
+    group.bench_function("fluent", |b| {
+        b.iter(|| {
+            black_box(Tuple::new()
+                .with("users")
+                .with(black_box(1234))
+                .with("posts")
+                .with(black_box(98765)))
+        })
+    });
+    group.bench_function("array_tuple", |b| {
+        b.iter(|| {
+            black_box(ArrayTuple::<64>::new()
+                .with("users")
+                .with(black_box(1234))
+                .with("posts")
+                .with(black_box(98765)))
+        })
+    });
+    group.bench_function("from_segments", |b| {
+        let segments = vec![Segment::Const("users"), Segment::Integer(1234), Segment::Const("posts"), Segment::Integer(98765)];
+
+        b.iter(|| black_box(Tuple::from_segments(&segments)))
+    });
+
+    group.finish();
+}
+
+fn bench_add_segment(c: &mut Criterion) {
+    let mut group = c.benchmark_group("add_segment");
+
+    for (name, segment) in segments() {
+        group.bench_with_input(BenchmarkId::from_parameter(name), &segment, |b, segment| {
+            b.iter_batched(|| Tuple::with_capacity(64), |mut tuple| {
+                tuple.add_segment(segment);
+                tuple
+            }, BatchSize::SmallInput)
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode");
+
+    let typical = tuple!("users", 1234, "posts", 98765, [7; 16], 1.5);
+    group.bench_function("typical", |b| b.iter(|| black_box(typical.as_segments().unwrap())));
+
+    for (name, segment) in segments() {
+        let tuple = Tuple::from_segments(&[segment]);
+        group.bench_with_input(BenchmarkId::new("segment", name), &tuple, |b, tuple| {
+            b.iter(|| black_box(tuple.as_segments().unwrap()))
+        });
+    }
+
+    let escapes = Tuple::from_segments(&[
+        Segment::Bytes(vec![0; 256]),
+        Segment::String(String::from("\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0")),
+        Segment::Bytes(vec![0; 256]),
+    ]);
+    group.bench_function("escape_heavy", |b| b.iter(|| black_box(escapes.as_segments().unwrap())));
+
+    for &depth in [4, 16, 32].iter() {
+        let tuple = Tuple::from_segments(&[nested(depth)]);
+        group.bench_with_input(BenchmarkId::new("nested", depth), &tuple, |b, tuple| {
+            b.iter(|| black_box(tuple.as_segments().unwrap()))
+        });
+    }
+
+    let wide = Tuple::from_segments(&(0..100).map(Segment::Integer).collect::<Vec<_>>());
+    group.bench_function("wide", |b| b.iter(|| black_box(wide.as_segments().unwrap())));
+    group.bench_function("wide_index", |b| {
+        b.iter(|| black_box(TupleIndex::new(wide.as_bytes()).unwrap().segment(black_box(50))))
+    });
+
+    group.finish();
+}
+
+fn bench_prefix(c: &mut Criterion) {
+    let mut group = c.benchmark_group("prefix");
+    let prefix = tuple!("tenants", 42, "users", 1234, "posts");
+
+    group.bench_function("add_builder", |b| {
+        b.iter(|| black_box(tuple!(&prefix, black_box(98765))))
+    });
+    group.bench_function("rebuild", |b| {
+        b.iter(|| black_box(tuple!("tenants", 42, "users", 1234, "posts", black_box(98765))))
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_construction, bench_add_segment, bench_decode, bench_prefix);
+criterion_main!(benches);
@@ -0,0 +1,387 @@
+//! Tuples stored inline
+//!
+//! A `Tuple` keeps its bytes on the heap, so building one allocates as soon as the first segment is
+//! added (and again as it grows), which shows up when building short keys in tight loops. An
+//! `ArrayTuple` keeps up to `N` encoded bytes on the stack, so short keys never allocate however
+//! many segments are added, and only moves them to the heap once a segment no longer fits.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use constants::*;
+use errors::TupleError;
+#[cfg(feature = "uuid")]
+use uuid::Uuid;
+use segment::Segment;
+use tuple_ref::TupleRef;
+use utils::{encode_byte_string, Output};
+use {AddToTuple, Tuple};
+
+/// A tuple builder which stores up to `N` encoded bytes inline, spilling to the heap beyond that
+///
+/// # Examples
+/// ```
+/// #[macro_use] extern crate binary_tuples;
+///
+/// use binary_tuples::{AddToTuple, ArrayTuple};
+///
+/// # fn main() {
+/// let key = ArrayTuple::<64>::new().with("users").with(1234);
+///
+/// assert!(key.is_inline());
+/// assert_eq!(key.as_bytes(), tuple!("users", 1234).as_bytes());
+///
+/// let key = key.with(vec![0; 64]);
+///
+/// assert!(!key.is_inline());
+/// assert_eq!(key.into_tuple(), tuple!("users", 1234, vec![0; 64]));
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ArrayTuple<const N: usize> {
+    storage: Storage<N>,
+}
+
+#[derive(Clone)]
+enum Storage<const N: usize> {
+    Inline { buffer: [u8; N], len: usize },
+    Heap(Vec<u8>),
+}
+
+impl<const N: usize> ArrayTuple<N> {
+    /// Create a new, empty tuple
+    pub fn new() -> ArrayTuple<N> {
+        ArrayTuple {
+            storage: Storage::Inline { buffer: [0; N], len: 0 },
+        }
+    }
+
+    /// Serialize a segment onto the end of the tuple
+    ///
+    /// # Panics
+    /// If a nested tuple within the segment contains a descending element (see
+    /// `try_add_segment`).
+    pub fn add_segment(&mut self, input: &Segment) {
+        input.write(&mut self.storage);
+    }
+
+    /// Serialize a segment onto the end of the tuple, failing with `TupleError::NestedDescending`
+    /// and leaving the tuple unchanged if a nested tuple within it contains a descending element
+    pub fn try_add_segment(&mut self, input: &Segment) -> Result<(), TupleError> {
+        input.check_nesting()?;
+        self.add_segment(input);
+
+        Ok(())
+    }
+
+    /// Directly embed the contents of another tuple builder in this builder
+    pub fn add_builder(&mut self, input: &Tuple) {
+        self.storage.extend_from_slice(input.as_bytes());
+    }
+
+    /// Return a serialized tuple
+    pub fn as_bytes(&self) -> &[u8] {
+        match self.storage {
+            Storage::Inline { ref buffer, len } => &buffer[..len],
+            Storage::Heap(ref buffer) => buffer,
+        }
+    }
+
+    /// The number of bytes in the encoded tuple
+    pub fn encoded_len(&self) -> usize {
+        self.as_bytes().len()
+    }
+
+    /// Whether the encoded tuple still fits in the inline buffer
+    pub fn is_inline(&self) -> bool {
+        match self.storage {
+            Storage::Inline { .. } => true,
+            Storage::Heap(_) => false,
+        }
+    }
+
+    /// Borrow the encoded tuple as a `TupleRef`
+    pub fn as_tuple_ref<'a>(&'a self) -> TupleRef<'a> {
+        TupleRef::new(self.as_bytes())
+    }
+
+    /// Convert into a heap allocated `Tuple`, reusing the buffer if the tuple has already spilled
+    pub fn into_tuple(self) -> Tuple {
+        match self.storage {
+            Storage::Inline { buffer, len } => Tuple::from_bytes(&buffer[..len]),
+            Storage::Heap(buffer) => Tuple::from(buffer),
+        }
+    }
+}
+
+impl<const N: usize> Storage<N> {
+    /// Move the encoded bytes to the heap, leaving room for at least `additional` more
+    fn spill(&mut self, additional: usize) {
+        if let Storage::Inline { ref buffer, len } = *self {
+            let mut heap = Vec::with_capacity(core::cmp::max(len + additional, 2 * N));
+            heap.extend_from_slice(&buffer[..len]);
+            *self = Storage::Heap(heap);
+        }
+    }
+}
+
+impl<const N: usize> Output for Storage<N> {
+    fn push(&mut self, value: u8) {
+        match *self {
+            Storage::Inline { ref mut buffer, ref mut len } if *len < N => {
+                buffer[*len] = value;
+                *len += 1;
+                return;
+            }
+            Storage::Heap(ref mut buffer) => {
+                buffer.push(value);
+                return;
+            }
+            _ => {}
+        }
+
+        self.spill(1);
+        self.push(value);
+    }
+
+    fn extend_from_slice(&mut self, values: &[u8]) {
+        match *self {
+            Storage::Inline { ref mut buffer, ref mut len } if *len + values.len() <= N => {
+                buffer[*len..*len + values.len()].copy_from_slice(values);
+                *len += values.len();
+                return;
+            }
+            Storage::Heap(ref mut buffer) => {
+                buffer.extend_from_slice(values);
+                return;
+            }
+            _ => {}
+        }
+
+        self.spill(values.len());
+        self.extend_from_slice(values);
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        match *self {
+            Storage::Inline { len, .. } if len + additional > N => self.spill(additional),
+            Storage::Heap(ref mut buffer) => buffer.reserve(additional),
+            _ => {}
+        }
+    }
+}
+
+impl<const N: usize> Default for ArrayTuple<N> {
+    fn default() -> ArrayTuple<N> {
+        ArrayTuple::new()
+    }
+}
+
+impl<const N: usize> fmt::Debug for ArrayTuple<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.as_tuple_ref().fmt_named(f, "ArrayTuple")
+    }
+}
+
+impl<const N: usize> PartialEq for ArrayTuple<N> {
+    fn eq(&self, other: &ArrayTuple<N>) -> bool {
+        self.as_bytes() == other.as_bytes()
+    }
+}
+
+impl<const N: usize> Eq for ArrayTuple<N> {}
+
+impl<const N: usize> PartialOrd for ArrayTuple<N> {
+    fn partial_cmp(&self, other: &ArrayTuple<N>) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<const N: usize> Ord for ArrayTuple<N> {
+    fn cmp(&self, other: &ArrayTuple<N>) -> Ordering {
+        self.as_bytes().cmp(other.as_bytes())
+    }
+}
+
+impl<const N: usize> Hash for ArrayTuple<N> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_bytes().hash(state)
+    }
+}
+
+impl<const N: usize> AsRef<[u8]> for ArrayTuple<N> {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl<const N: usize> From<ArrayTuple<N>> for Tuple {
+    fn from(tuple: ArrayTuple<N>) -> Tuple {
+        tuple.into_tuple()
+    }
+}
+
+impl<const N: usize> AddToTuple<i64> for ArrayTuple<N> {
+    fn add(&mut self, v: i64) {
+        self.add_segment(&Segment::Integer(v));
+    }
+}
+
+impl<const N: usize> AddToTuple<String> for ArrayTuple<N> {
+    fn add(&mut self, v: String) {
+        encode_byte_string(STRING_CODE, v.as_bytes(), &mut self.storage);
+    }
+}
+
+impl<'a, const N: usize> AddToTuple<&'a [u8]> for ArrayTuple<N> {
+    fn add(&mut self, v: &'a [u8]) {
+        encode_byte_string(BYTES_CODE, v, &mut self.storage);
+    }
+}
+
+impl<'a, const N: usize> AddToTuple<&'a Vec<u8>> for ArrayTuple<N> {
+    fn add(&mut self, v: &'a Vec<u8>) {
+        encode_byte_string(BYTES_CODE, v, &mut self.storage);
+    }
+}
+
+impl<const N: usize> AddToTuple<f32> for ArrayTuple<N> {
+    fn add(&mut self, v: f32) {
+        self.add_segment(&Segment::Float(v));
+    }
+}
+
+impl<const N: usize> AddToTuple<f64> for ArrayTuple<N> {
+    fn add(&mut self, v: f64) {
+        self.add_segment(&Segment::Double(v));
+    }
+}
+
+impl<const N: usize> AddToTuple<Vec<u8>> for ArrayTuple<N> {
+    fn add(&mut self, v: Vec<u8>) {
+        encode_byte_string(BYTES_CODE, &v, &mut self.storage);
+    }
+}
+
+impl<const N: usize> AddToTuple<&'static str> for ArrayTuple<N> {
+    fn add(&mut self, v: &'static str) {
+        self.add_segment(&Segment::Const(v));
+    }
+}
+
+impl<const N: usize> AddToTuple<[u8; 16]> for ArrayTuple<N> {
+    fn add(&mut self, v: [u8; 16]) {
+        self.add_segment(&Segment::UUID(v));
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl<const N: usize> AddToTuple<Uuid> for ArrayTuple<N> {
+    fn add(&mut self, v: Uuid) {
+        self.add_segment(&Segment::UUID(v.into_bytes()));
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl<'a, const N: usize> AddToTuple<&'a Uuid> for ArrayTuple<N> {
+    fn add(&mut self, v: &'a Uuid) {
+        self.add_segment(&Segment::UUID(*v.as_bytes()));
+    }
+}
+
+impl<const N: usize> AddToTuple<Vec<Segment>> for ArrayTuple<N> {
+    /// Add the segments as a nested tuple
+    ///
+    /// # Panics
+    /// If the segments contain a descending element (see `ArrayTuple::try_add_segment`).
+    fn add(&mut self, v: Vec<Segment>) {
+        self.add_segment(&Segment::Nested(v));
+    }
+}
+
+impl<'a, const N: usize> AddToTuple<&'a Tuple> for ArrayTuple<N> {
+    fn add(&mut self, v: &'a Tuple) {
+        self.add_builder(v);
+    }
+}
+
+impl<const N: usize> AddToTuple<Tuple> for ArrayTuple<N> {
+    fn add(&mut self, v: Tuple) {
+        self.add_builder(&v);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::boxed::Box;
+
+    #[test]
+    fn test_inline() {
+        let key = ArrayTuple::<16>::new().with("users").with(1234).with(vec![0, 1]);
+
+        assert!(key.is_inline());
+        assert_eq!(key.as_bytes(), ::tuple!("users", 1234, vec![0, 1]).as_bytes());
+        assert_eq!(key.as_tuple_ref().as_segments(), ::tuple!("users", 1234, vec![0, 1]).as_segments());
+    }
+
+    #[test]
+    fn test_spill() {
+        let mut key = ArrayTuple::<8>::new();
+        key.add("users");
+        assert!(key.is_inline());
+
+        key.add(String::from("posts"));
+        assert!(!key.is_inline());
+
+        key.add(1234);
+        assert_eq!(key.into_tuple(), ::tuple!("users", "posts", 1234));
+    }
+
+    #[test]
+    fn test_spill_while_escaping() {
+        let bytes = [0u8; 6];
+        let key = ArrayTuple::<8>::new().with(&bytes[..]);
+
+        assert!(!key.is_inline());
+        assert_eq!(key.as_bytes(), ::tuple!(&bytes[..]).as_bytes());
+    }
+
+    #[test]
+    fn test_matches_tuple() {
+        let segments = vec![Segment::Const("tags"), Segment::Nested(vec![Segment::Integer(3)])];
+        let tuple = ::tuple!(1.5f32, 2.5f64, [7u8; 16], segments.clone(), &vec![1u8], ::tuple!(7));
+        let key = ArrayTuple::<128>::new()
+            .with(1.5f32)
+            .with(2.5f64)
+            .with([7u8; 16])
+            .with(segments)
+            .with(&vec![1u8])
+            .with(::tuple!(7));
+
+        assert!(key.is_inline());
+        assert_eq!(Tuple::from(key), tuple);
+    }
+
+    #[test]
+    fn test_try_add_segment() {
+        let mut key = ArrayTuple::<16>::new().with("a");
+
+        assert_eq!(key.try_add_segment(&Segment::Nested(vec![Segment::Descending(Box::new(Segment::Integer(1)))])), Err(TupleError::NestedDescending));
+        assert_eq!(key.try_add_segment(&Segment::Integer(1)), Ok(()));
+        assert_eq!(key.as_bytes(), ::tuple!("a", 1).as_bytes());
+    }
+
+    #[test]
+    fn test_traits() {
+        let a = ArrayTuple::<4>::new().with("a");
+        let b = ArrayTuple::<4>::new().with("users");
+
+        assert!(a < b);
+        assert_eq!(b, ArrayTuple::<4>::new().with(String::from("users")));
+        assert_eq!(format!("{:?}", b), "ArrayTuple(\"users\")");
+        assert!(ArrayTuple::<0>::default().as_bytes().is_empty());
+    }
+}
@@ -0,0 +1,227 @@
+//! Command line tool for working with encoded tuple keys
+//!
+//! Keys can be supplied as hex (`027573657273001501`), as FoundationDB style escaped
+//! printable strings (`\x02users\x00\x15\x01`) or as textual tuples (`("users", 1)`).
+
+extern crate binary_tuples;
+
+use std::env;
+use std::io;
+use std::io::BufRead;
+use std::process;
+
+use binary_tuples::{printable, text, Tuple, TupleError, segment::Segment};
+
+const USAGE: &str = "usage: tuple <command> [options] [arguments]
+
+commands:
+    decode [KEY...]     decode keys into their textual form, followed by any bytes which aren't part of a
+                        valid tuple (reads lines from stdin if no KEY is given)
+    encode TUPLE        encode a textual tuple such as '(\"users\", 1)' as hex and escaped bytes
+    range KEY           print the begin (inclusive) and end (exclusive) keys of the range prefixed by KEY
+    diff KEY KEY        compare two keys element by element
+    dump KEY            print every byte of a key labelled with the element it belongs to
+
+options:
+    -x, --hex           read keys as hex
+    -p, --printable     read keys as escaped printable strings (\\xNN)
+    -t, --tuple         read keys as textual tuples
+
+By default the key format is detected from its contents.";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Auto,
+    Hex,
+    Printable,
+    Text,
+}
+
+fn main() {
+    let mut format = Format::Auto;
+    let mut arguments = Vec::new();
+
+    for argument in env::args().skip(1) {
+        match argument.as_str() {
+            "-x" | "--hex" => format = Format::Hex,
+            "-p" | "--printable" => format = Format::Printable,
+            "-t" | "--tuple" => format = Format::Text,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ => arguments.push(argument),
+        }
+    }
+
+    let result = match arguments.split_first() {
+        Some((command, rest)) => match (command.as_str(), rest) {
+            ("decode", keys) => decode(format, keys),
+            ("encode", [tuple]) => encode(tuple),
+            ("range", [key]) => range(format, key),
+            ("diff", [left, right]) => diff(format, left, right),
+            ("dump", [key]) => dump(format, key),
+            _ => usage(),
+        },
+        None => usage(),
+    };
+
+    match result {
+        Ok(true) => (),
+        Ok(false) => process::exit(1),
+        Err(err) => {
+            eprintln!("tuple: {}", err);
+            process::exit(2);
+        }
+    }
+}
+
+fn usage() -> Result<bool, String> {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+fn decode(format: Format, keys: &[String]) -> Result<bool, String> {
+    if !keys.is_empty() {
+        for key in keys {
+            println!("{}", decode_key(format, key)?);
+        }
+
+        return Ok(true);
+    }
+
+    let stdin = io::stdin();
+    for line in stdin.lock().lines() {
+        let line = line.map_err(|err| err.to_string())?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        match decode_key(format, line) {
+            Ok(value) => println!("{}", value),
+            Err(err) => println!("<{}>", err),
+        }
+    }
+
+    Ok(true)
+}
+
+/// Decode as much of the key as possible, appending anything which isn't a valid tuple in its
+/// escaped printable form
+fn decode_key(format: Format, key: &str) -> Result<String, String> {
+    let tuple = read_key(format, key)?;
+    let (segments, unknown) = tuple.as_segments_lenient();
+
+    let mut output = text::format(&segments);
+    if let Some(unknown) = unknown {
+        output.push_str(&format!(" <unknown at {}: {}>", unknown.position, printable::printable(unknown.bytes)));
+    }
+
+    Ok(output)
+}
+
+fn encode(tuple: &str) -> Result<bool, String> {
+    let tuple = tuple.parse::<Tuple>().map_err(describe)?;
+
+    println!("{}", to_hex(tuple.as_bytes()));
+    println!("{}", printable::printable(tuple.as_bytes()));
+    Ok(true)
+}
+
+fn range(format: Format, key: &str) -> Result<bool, String> {
+    let (begin, end) = read_key(format, key)?.range();
+
+    println!("begin: {}", to_hex(&begin));
+    println!("end:   {}", to_hex(&end));
+    Ok(true)
+}
+
+fn diff(format: Format, left: &str, right: &str) -> Result<bool, String> {
+    let left = read_key(format, left)?.as_segments().map_err(describe)?;
+    let right = read_key(format, right)?.as_segments().map_err(describe)?;
+
+    let mut equal = true;
+    for index in 0..left.len().max(right.len()) {
+        let (marker, left, right) = match (left.get(index), right.get(index)) {
+            (Some(a), Some(b)) if a == b => ("=", a.to_string(), b.to_string()),
+            (a, b) => {
+                equal = false;
+                ("!", describe_element(a), describe_element(b))
+            }
+        };
+
+        if marker == "=" {
+            println!("{} {:>3}: {}", marker, index, left);
+        } else {
+            println!("{} {:>3}: {} | {}", marker, index, left, right);
+        }
+    }
+
+    Ok(equal)
+}
+
+fn dump(format: Format, key: &str) -> Result<bool, String> {
+    let key = read_key(format, key)?;
+
+    println!("{}", printable::printable(key.as_bytes()));
+    print!("{}", printable::hex_dump(key.as_bytes()));
+    Ok(true)
+}
+
+fn describe_element(segment: Option<&Segment>) -> String {
+    segment.map_or_else(|| String::from("<missing>"), |segment| segment.to_string())
+}
+
+fn describe(err: TupleError) -> String {
+    err.to_string()
+}
+
+fn read_key(format: Format, key: &str) -> Result<Tuple, String> {
+    let format = match format {
+        Format::Auto if key.trim_start().starts_with('(') => Format::Text,
+        Format::Auto if is_hex(key) => Format::Hex,
+        Format::Auto => Format::Printable,
+        format => format,
+    };
+
+    match format {
+        Format::Text => key.parse::<Tuple>().map_err(describe),
+        Format::Hex => from_hex(key).map(|bytes| Tuple::from_bytes(&bytes)),
+        _ => printable::from_printable(key).map(|bytes| Tuple::from_bytes(&bytes)).map_err(describe),
+    }
+}
+
+fn strip_hex_prefix(key: &str) -> &str {
+    let key = key.trim();
+    if key.starts_with("0x") || key.starts_with("0X") {
+        &key[2..]
+    } else {
+        key
+    }
+}
+
+fn is_hex(key: &str) -> bool {
+    let digits = strip_hex_prefix(key).bytes()
+        .filter(|c| !c.is_ascii_whitespace())
+        .collect::<Vec<_>>();
+
+    !digits.is_empty() && digits.len() % 2 == 0 && digits.iter().all(|c| c.is_ascii_hexdigit())
+}
+
+fn from_hex(key: &str) -> Result<Vec<u8>, String> {
+    let digits = strip_hex_prefix(key).chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| c.to_digit(16).map(|value| value as u8).ok_or_else(|| format!("invalid hex digit '{}'", c)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if digits.len() % 2 != 0 {
+        return Err(String::from("hex keys must contain an even number of digits"));
+    }
+
+    Ok(digits.chunks(2).map(|pair| pair[0] << 4 | pair[1]).collect())
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
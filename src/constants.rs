@@ -1,5 +1,3 @@
-use std::u64::MAX as MAX_VALUE;
-
 pub const BYTES_CODE: u8 = 0x01;
 pub const STRING_CODE: u8 = 0x02;
 pub const NESTED_CODE: u8 = 0x05;
@@ -19,12 +17,12 @@ pub const NULL_ESCAPE: u8 = 0xFF;
 
 pub const SIZE_LIMITS: [u64; 9] = [
     0,
-    (1 << (1 * 8)) - 1,
+    (1 << 8) - 1,
     (1 << (2 * 8)) - 1,
     (1 << (3 * 8)) - 1,
     (1 << (4 * 8)) - 1,
     (1 << (5 * 8)) - 1,
     (1 << (6 * 8)) - 1,
     (1 << (7 * 8)) - 1,
-    MAX_VALUE,
+    u64::MAX,
 ];
@@ -0,0 +1,508 @@
+//! Limits for decoding untrusted input
+//!
+//! Every decode entry point honors a set of `DecodeOptions`. `Tuple::as_segments` and the other
+//! convenience methods use the defaults, which only bound the nesting depth - use
+//! `DecodeOptions::decode` directly to also bound the number of elements and the size of the input.
+//!
+//! The options can also enable strict mode, which rejects any input that isn't in its canonical
+//! form - so that two different byte strings never decode to the same segments.
+//!
+//! `DecodeOptions::decode_lenient` decodes as much of a tuple as it can instead of failing,
+//! returning anything it couldn't decode as `Unknown` bytes.
+
+use alloc::vec::Vec;
+use core::fmt;
+use constants::*;
+use errors::TupleError;
+use segment;
+use segment::Segment;
+use utils::{byte_string_end, fixed_len};
+
+/// The default maximum nesting depth of tuples
+pub const DEFAULT_MAX_DEPTH: usize = 128;
+
+/// A limit which may be exceeded while decoding
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Limit {
+    /// The number of tuples nested inside each other
+    Depth,
+    /// The total number of elements, including the elements of nested tuples
+    Elements,
+    /// The size of the encoded input
+    Bytes,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Limit::Depth => write!(f, "nesting depth"),
+            Limit::Elements => write!(f, "element count"),
+            Limit::Bytes => write!(f, "input size"),
+        }
+    }
+}
+
+/// The remainder of a tuple which couldn't be decoded by `DecodeOptions::decode_lenient`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Unknown<'a> {
+    /// The position of the first byte which couldn't be decoded
+    pub position: usize,
+    /// The bytes from `position` to the end of the input
+    pub bytes: &'a [u8],
+}
+
+/// Limits applied while decoding tuples
+///
+/// # Examples
+/// ```
+/// use binary_tuples::{DecodeOptions, Limit, TupleError};
+///
+/// let options = DecodeOptions::new().max_depth(2);
+///
+/// assert!(options.decode(&[5, 5, 0, 0]).is_ok());
+/// assert_eq!(options.decode(&[5, 5, 5, 0, 0, 0]), Err(TupleError::LimitExceeded { limit: Limit::Depth, position: 2 }));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DecodeOptions {
+    max_depth: usize,
+    max_elements: usize,
+    max_bytes: usize,
+    strict: bool,
+}
+
+impl DecodeOptions {
+    /// Create the default options - a maximum depth of `DEFAULT_MAX_DEPTH`, with no limits on the
+    /// number of elements or the size of the input
+    pub fn new() -> DecodeOptions {
+        DecodeOptions {
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_elements: usize::MAX,
+            max_bytes: usize::MAX,
+            strict: false,
+        }
+    }
+
+    /// Limit the number of tuples nested inside each other, where a tuple without any nested
+    /// tuples has a depth of 0
+    pub fn max_depth(mut self, max_depth: usize) -> DecodeOptions {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Limit the total number of elements, including the elements of nested tuples
+    pub fn max_elements(mut self, max_elements: usize) -> DecodeOptions {
+        self.max_elements = max_elements;
+        self
+    }
+
+    /// Limit the size of the encoded input in bytes
+    pub fn max_bytes(mut self, max_bytes: usize) -> DecodeOptions {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Reject input which isn't canonically encoded, such as integers with leading zero bytes
+    ///
+    /// The decoded segments are encoded again and compared against the input, so this roughly
+    /// doubles the cost of decoding.
+    pub fn strict(mut self, strict: bool) -> DecodeOptions {
+        self.strict = strict;
+        self
+    }
+
+    /// Decode a tuple into its segments
+    ///
+    /// Fails with `TupleError::LimitExceeded` if any of the limits are exceeded, and in strict mode
+    /// with `TupleError::NonCanonical` if the input isn't canonically encoded.
+    pub fn decode(&self, input: &[u8]) -> Result<Vec<Segment>, TupleError> {
+        self.check_bytes(input.len())?;
+
+        let mut state = DecodeState { options: self, elements: 0 };
+        let (segments, read) = Segment::decode_segments(input, 0, 0, &mut state)?;
+
+        if read != input.len() {
+            return Err(TupleError::TruncatedTuple);
+        }
+
+        if self.strict {
+            check_canonical(input, 0, &segments)?;
+        }
+
+        Ok(segments)
+    }
+
+    /// Decode as many elements as possible, returning the decoded segments along with the
+    /// remainder of the input from the first element which couldn't be decoded
+    ///
+    /// An element fails to decode if it is invalid, truncated or exceeds any of the limits (the
+    /// size limit applies to the position of the end of each element).
+    ///
+    /// # Examples
+    /// ```
+    /// use binary_tuples::{DecodeOptions, Unknown, segment::Segment};
+    ///
+    /// let (segments, unknown) = DecodeOptions::new().decode_lenient(&[21, 1, 0xFF, 1]);
+    ///
+    /// assert_eq!(segments, vec![Segment::Integer(1)]);
+    /// assert_eq!(unknown, Some(Unknown { position: 2, bytes: &[0xFF, 1] }));
+    /// ```
+    pub fn decode_lenient<'a>(&self, input: &'a [u8]) -> (Vec<Segment>, Option<Unknown<'a>>) {
+        let (segments, read) = self.decode_prefix(input);
+
+        let unknown = if read < input.len() {
+            Some(Unknown { position: read, bytes: &input[read..] })
+        } else {
+            None
+        };
+
+        (segments, unknown)
+    }
+
+    /// Decode the longest prefix of the input made up of valid elements, returning the decoded
+    /// segments along with the number of bytes they were decoded from
+    ///
+    /// # Examples
+    /// ```
+    /// use binary_tuples::{DecodeOptions, segment::Segment};
+    ///
+    /// let (segments, read) = DecodeOptions::new().decode_prefix(&[21, 1, 0, 0xFF]);
+    ///
+    /// assert_eq!(segments, vec![Segment::Integer(1)]);
+    /// assert_eq!(read, 2);
+    /// ```
+    pub fn decode_prefix(&self, input: &[u8]) -> (Vec<Segment>, usize) {
+        let mut segments = Vec::new();
+        let mut state = DecodeState { options: self, elements: 0 };
+        let mut index = 0;
+
+        while index < input.len() {
+            match self.decode_element(input, index, &mut state) {
+                Ok((element, end)) => {
+                    segments.extend(element);
+                    index = end;
+                }
+                Err(_) => break,
+            }
+        }
+
+        (segments, index)
+    }
+
+    /// Decode exactly `count` elements from the start of the input, returning the decoded segments
+    /// along with the rest of the input
+    ///
+    /// Fails if the input starts with fewer than `count` valid elements.
+    ///
+    /// # Examples
+    /// ```
+    /// use binary_tuples::{DecodeOptions, segment::Segment};
+    ///
+    /// let (segments, rest) = DecodeOptions::new().decode_exact(&[21, 1, 21, 2, 21, 3], 2).unwrap();
+    ///
+    /// assert_eq!(segments, vec![Segment::Integer(1), Segment::Integer(2)]);
+    /// assert_eq!(rest, &[21, 3]);
+    /// ```
+    pub fn decode_exact<'a>(&self, input: &'a [u8], count: usize) -> Result<(Vec<Segment>, &'a [u8]), TupleError> {
+        // Every element takes at least a byte, which bounds the capacity for huge counts
+        let mut segments = Vec::with_capacity(count.min(input.len()));
+        let mut state = DecodeState { options: self, elements: 0 };
+        let mut index = 0;
+
+        for _ in 0..count {
+            let (element, end) = self.decode_element(input, index, &mut state)?;
+            segments.extend(element);
+            index = end;
+        }
+
+        Ok((segments, &input[index..]))
+    }
+
+    /// Decode the single top level element starting at `index`, returning it along with the
+    /// position following it
+    pub(crate) fn decode_element(&self, input: &[u8], index: usize, state: &mut DecodeState) -> Result<(Vec<Segment>, usize), TupleError> {
+        let end = self.find_element_end(input, index)?;
+
+        let (segments, _) = Segment::decode_segments(&input[..end], index, 0, state)?;
+        if self.strict {
+            check_canonical(&input[index..end], index, &segments)?;
+        }
+
+        Ok((segments, end))
+    }
+
+    /// Find the end of the top level element starting at `index` without decoding it, failing if
+    /// the element is invalid or exceeds the depth or size limits
+    pub(crate) fn find_element_end(&self, input: &[u8], index: usize) -> Result<usize, TupleError> {
+        match element_end(input, index, self.max_depth) {
+            Some(end) => {
+                self.check_bytes(end)?;
+                Ok(end)
+            }
+            None => {
+                // Decode the rest of the input to find out why the element is invalid
+                let mut state = DecodeState { options: self, elements: 0 };
+                Segment::decode_segments(input, index, 0, &mut state)?;
+                Err(TupleError::TruncatedTuple)
+            }
+        }
+    }
+
+    pub(crate) fn check_bytes(&self, length: usize) -> Result<(), TupleError> {
+        if length > self.max_bytes {
+            Err(TupleError::LimitExceeded { limit: Limit::Bytes, position: self.max_bytes })
+        } else {
+            Ok(())
+        }
+    }
+
+    pub(crate) fn check_depth(&self, depth: usize, position: usize) -> Result<(), TupleError> {
+        if depth > self.max_depth {
+            Err(TupleError::LimitExceeded { limit: Limit::Depth, position })
+        } else {
+            Ok(())
+        }
+    }
+
+    pub(crate) fn check_elements(&self, elements: usize, position: usize) -> Result<(), TupleError> {
+        if elements > self.max_elements {
+            Err(TupleError::LimitExceeded { limit: Limit::Elements, position })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Find the end of the element starting at `start` without decoding it, returning `None` if the
+/// element is invalid, truncated or nested more than `max_depth` deep
+pub(crate) fn element_end(input: &[u8], start: usize, max_depth: usize) -> Option<usize> {
+    masked_element_end(input, start, max_depth, 0)
+}
+
+/// Find the end of the descending element starting at `start`, whose bytes are all inverted
+pub(crate) fn descending_element_end(input: &[u8], start: usize, max_depth: usize) -> Option<usize> {
+    let end = masked_element_end(input, start, max_depth, 0xFF)?;
+
+    match !input[start] {
+        // Descending byte strings end with a second null
+        BYTES_CODE | STRING_CODE if input.get(end) == Some(&!NULL) => Some(end + 1),
+        BYTES_CODE | STRING_CODE => None,
+        _ => Some(end),
+    }
+}
+
+fn masked_element_end(input: &[u8], start: usize, max_depth: usize, mask: u8) -> Option<usize> {
+    let mut index = start;
+    let mut depth = 0;
+
+    loop {
+        let code = *input.get(index)? ^ mask;
+        index += 1;
+
+        match code {
+            NULL if depth == 0 => return None,
+            NULL => depth -= 1,
+            NESTED_CODE if depth == max_depth => return None,
+            NESTED_CODE => depth += 1,
+            BYTES_CODE | STRING_CODE => index += byte_string_end(&input[index..], mask)?,
+            type_code => index += fixed_len(type_code)?,
+        }
+
+        if index > input.len() {
+            return None;
+        }
+
+        if depth == 0 {
+            return Some(index);
+        }
+    }
+}
+
+/// Check that encoding the segments produces exactly the input, failing at the first difference
+/// (where the input starts at `offset`)
+fn check_canonical(input: &[u8], offset: usize, segments: &[Segment]) -> Result<(), TupleError> {
+    let mut encoded = Vec::with_capacity(input.len());
+    segment::encode_slice(segments, &mut encoded);
+
+    if encoded == input {
+        return Ok(());
+    }
+
+    let position = input.iter()
+        .zip(encoded.iter())
+        .position(|(a, b)| a != b)
+        .unwrap_or_else(|| input.len().min(encoded.len()));
+
+    Err(TupleError::NonCanonical { position: offset + position })
+}
+
+impl Default for DecodeOptions {
+    fn default() -> DecodeOptions {
+        DecodeOptions::new()
+    }
+}
+
+/// The progress of a single decode, checked against the options as elements are read
+pub(crate) struct DecodeState<'a> {
+    pub options: &'a DecodeOptions,
+    pub elements: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::String;
+
+    fn nested(depth: usize) -> Vec<u8> {
+        let mut input = vec![NESTED_CODE; depth];
+        input.push(INT_ZERO_CODE);
+        input.extend(vec![NULL; depth]);
+        input
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let options = DecodeOptions::new().max_depth(3);
+
+        assert!(options.decode(&nested(3)).is_ok());
+        assert_eq!(options.decode(&nested(4)), Err(TupleError::LimitExceeded { limit: Limit::Depth, position: 3 }));
+        assert!(DecodeOptions::new().max_depth(0).decode(&[INT_ZERO_CODE]).is_ok());
+    }
+
+    #[test]
+    fn test_default_max_depth() {
+        let input = vec![NESTED_CODE; 100_000];
+
+        assert_eq!(Segment::decode(&input), Err(TupleError::LimitExceeded { limit: Limit::Depth, position: DEFAULT_MAX_DEPTH }));
+        assert!(Segment::decode(&nested(DEFAULT_MAX_DEPTH)).is_ok());
+    }
+
+    #[test]
+    fn test_max_elements() {
+        let input = [INT_ZERO_CODE, NESTED_CODE, TRUE_CODE, FALSE_CODE, NULL];
+        let options = DecodeOptions::new().max_elements(4);
+
+        assert!(options.decode(&input).is_ok());
+        assert_eq!(options.max_elements(3).decode(&input), Err(TupleError::LimitExceeded { limit: Limit::Elements, position: 3 }));
+    }
+
+    #[test]
+    fn test_strict() {
+        let options = DecodeOptions::new().strict(true);
+
+        assert!(options.decode(&[STRING_CODE, 97, NULL, NESTED_CODE, INT_ZERO_CODE + 1, 1, NULL]).is_ok());
+        assert_eq!(options.decode(&[INT_ZERO_CODE + 2, 0, 1]), Err(TupleError::NonCanonical { position: 0 }));
+        assert_eq!(options.decode(&[TRUE_CODE, INT_ZERO_CODE - 2, 0xFF, 0xFE]), Err(TupleError::NonCanonical { position: 1 }));
+        assert_eq!(options.decode(&[STRING_CODE, 97, NULL, BYTES_CODE, 1]), Err(TupleError::TruncatedTuple));
+        assert!(DecodeOptions::new().decode(&[INT_ZERO_CODE + 2, 0, 1]).is_ok());
+    }
+
+    #[test]
+    fn test_element_end() {
+        let input = [STRING_CODE, 97, NULL, NESTED_CODE, INT_ZERO_CODE + 1, 1, NESTED_CODE, NULL, NULL, TRUE_CODE];
+
+        assert_eq!(element_end(&input, 0, 1), Some(3));
+        assert_eq!(element_end(&input, 3, 2), Some(9));
+        assert_eq!(element_end(&input, 3, 1), None);
+        assert_eq!(element_end(&input, 9, 0), Some(10));
+        assert_eq!(element_end(&input, 8, 0), None);
+        assert_eq!(element_end(&[INT_ZERO_CODE + 2, 1], 0, 0), None);
+        assert_eq!(element_end(&[0x99], 0, 0), None);
+    }
+
+    #[test]
+    fn test_descending_element_end() {
+        let input = ::tuple!(::Descending("a\0"), ::Descending(5), ::Descending(vec![Segment::Boolean(true)])).into_bytes();
+
+        assert_eq!(descending_element_end(&input, 0, 0), Some(6));
+        assert_eq!(descending_element_end(&input, 6, 0), Some(8));
+        assert_eq!(descending_element_end(&input, 8, 1), Some(11));
+        assert_eq!(descending_element_end(&input, 8, 0), None);
+        assert_eq!(descending_element_end(&input[..5], 0, 0), None);
+        assert_eq!(descending_element_end(&[INT_ZERO_CODE], 0, 0), None);
+    }
+
+    #[test]
+    fn test_decode_lenient() {
+        let input = [STRING_CODE, 97, NULL, INT_ZERO_CODE, 0x99, TRUE_CODE];
+        let (segments, unknown) = DecodeOptions::new().decode_lenient(&input);
+
+        assert_eq!(segments, vec![Segment::String(String::from("a")), Segment::Integer(0)]);
+        assert_eq!(unknown, Some(Unknown { position: 4, bytes: &[0x99, TRUE_CODE] }));
+
+        assert_eq!(DecodeOptions::new().decode_lenient(&[TRUE_CODE]), (vec![Segment::Boolean(true)], None));
+        assert_eq!(DecodeOptions::new().decode_lenient(&[]), (vec![], None));
+    }
+
+    #[test]
+    fn test_decode_lenient_invalid_elements() {
+        let (segments, unknown) = DecodeOptions::new().decode_lenient(&[TRUE_CODE, STRING_CODE, 0xC3, NULL]);
+        assert_eq!(segments, vec![Segment::Boolean(true)]);
+        assert_eq!(unknown, Some(Unknown { position: 1, bytes: &[STRING_CODE, 0xC3, NULL] }));
+
+        let (segments, unknown) = DecodeOptions::new().decode_lenient(&[TRUE_CODE, NESTED_CODE, TRUE_CODE]);
+        assert_eq!(segments, vec![Segment::Boolean(true)]);
+        assert_eq!(unknown.unwrap().position, 1);
+    }
+
+    #[test]
+    fn test_decode_lenient_limits() {
+        let input = [TRUE_CODE, FALSE_CODE, NESTED_CODE, TRUE_CODE, NULL, INT_ZERO_CODE + 2, 0, 1];
+
+        let (segments, unknown) = DecodeOptions::new().max_elements(3).decode_lenient(&input);
+        assert_eq!(segments, vec![Segment::Boolean(true), Segment::Boolean(false)]);
+        assert_eq!(unknown.unwrap().position, 2);
+
+        let (segments, unknown) = DecodeOptions::new().max_depth(0).decode_lenient(&input);
+        assert_eq!(segments.len(), 2);
+        assert_eq!(unknown.unwrap().position, 2);
+
+        let (segments, unknown) = DecodeOptions::new().max_bytes(6).decode_lenient(&input);
+        assert_eq!(segments.len(), 3);
+        assert_eq!(unknown.unwrap().position, 5);
+
+        let (segments, unknown) = DecodeOptions::new().strict(true).decode_lenient(&input);
+        assert_eq!(segments.len(), 3);
+        assert_eq!(unknown.unwrap().position, 5);
+    }
+
+    #[test]
+    fn test_decode_prefix() {
+        let input = [STRING_CODE, 97, NULL, NESTED_CODE, TRUE_CODE, NULL, NULL, 0xFF];
+
+        assert_eq!(DecodeOptions::new().decode_prefix(&input), (vec![
+            Segment::String(String::from("a")),
+            Segment::Nested(vec![Segment::Boolean(true)]),
+        ], 6));
+        assert_eq!(DecodeOptions::new().decode_prefix(&input[..6]).1, 6);
+        assert_eq!(DecodeOptions::new().decode_prefix(&input[..5]).1, 3);
+        assert_eq!(DecodeOptions::new().decode_prefix(&[]), (vec![], 0));
+    }
+
+    #[test]
+    fn test_decode_exact() {
+        let input = [INT_ZERO_CODE, NESTED_CODE, TRUE_CODE, NULL, 0x99, 1, 2];
+        let options = DecodeOptions::new();
+
+        assert_eq!(options.decode_exact(&input, 0), Ok((vec![], &input[..])));
+        assert_eq!(options.decode_exact(&input, 2), Ok((vec![
+            Segment::Integer(0),
+            Segment::Nested(vec![Segment::Boolean(true)]),
+        ], &input[4..])));
+        assert_eq!(options.decode_exact(&input, 3), Err(TupleError::DecodeError { position: 4, type_code: 0x99 }));
+        assert_eq!(options.decode_exact(&input[..2], 2), Err(TupleError::TruncatedNestedTuple));
+        assert_eq!(options.decode_exact(&input[..1], 2), Err(TupleError::TruncatedTuple));
+        assert_eq!(options.decode_exact(&[], usize::MAX), Err(TupleError::TruncatedTuple));
+        assert_eq!(options.decode_exact(&input, usize::MAX), Err(TupleError::DecodeError { position: 4, type_code: 0x99 }));
+        assert_eq!(options.max_depth(0).decode_exact(&input, 2), Err(TupleError::LimitExceeded { limit: Limit::Depth, position: 1 }));
+        assert_eq!(options.max_bytes(3).decode_exact(&input, 2), Err(TupleError::LimitExceeded { limit: Limit::Bytes, position: 3 }));
+        assert_eq!(options.strict(true).decode_exact(&[TRUE_CODE, INT_ZERO_CODE + 2, 0, 1], 2), Err(TupleError::NonCanonical { position: 1 }));
+    }
+
+    #[test]
+    fn test_max_bytes() {
+        let input = [INT_ZERO_CODE, TRUE_CODE, FALSE_CODE];
+
+        assert!(DecodeOptions::new().max_bytes(3).decode(&input).is_ok());
+        assert_eq!(DecodeOptions::new().max_bytes(2).decode(&input), Err(TupleError::LimitExceeded { limit: Limit::Bytes, position: 2 }));
+    }
+}
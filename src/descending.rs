@@ -0,0 +1,225 @@
+//! Elements which sort in descending order
+//!
+//! Inverting the bits of an encoded element reverses the order it sorts in, as long as no encoding
+//! is a prefix of another - strings and bytes get an extra terminating null to ensure this, as
+//! their escaped nulls would otherwise extend a shorter value. Wrapping a value in `Descending`
+//! stores it this way, which lets keys such as `(user, Descending(timestamp))` list the newest
+//! entries first - and unlike negating integers by hand, it works for strings and bytes too.
+//!
+//! Decoding inverted elements as part of a tuple needs a `TupleSchema` which marks their positions
+//! as descending. The store, map and index readers (and `Debug` output) instead pick them out by
+//! their inverted type codes, so a store holding descending keys can still be scanned without one -
+//! their `_with` variants also check the keys against a schema.
+
+use alloc::boxed::Box;
+use alloc::vec::{IntoIter, Vec};
+use array_tuple::ArrayTuple;
+use constants::NULL;
+use decode::{DecodeOptions, DecodeState};
+use errors::TupleError;
+use pack::{FromTuple, TuplePack};
+use schema::ElementSchema;
+use segment::{Segment, SegmentType};
+use {AddToTuple, Tuple};
+
+/// A value which is encoded to sort in descending order
+///
+/// Values which pack to several elements, such as Rust tuples, have each element stored in
+/// descending order. Values are read back by decoding with a schema, then unpacking the segments.
+///
+/// # Examples
+/// ```
+/// #[macro_use] extern crate binary_tuples;
+///
+/// use binary_tuples::{Descending, FromTuple, TupleSchema};
+/// use binary_tuples::segment::SegmentType;
+///
+/// # fn main() {
+/// let older = tuple!("posts", Descending(1000));
+/// let newer = tuple!("posts", Descending(2000));
+///
+/// assert!(newer.as_bytes() < older.as_bytes());
+///
+/// let schema = TupleSchema::new()
+///     .element(SegmentType::String)
+///     .element(Descending(SegmentType::Integer));
+/// let segments = schema.decode(newer.as_bytes()).unwrap();
+///
+/// assert_eq!(<(String, Descending<i64>)>::from_segments(segments), Ok((String::from("posts"), Descending(2000))));
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Descending<T>(pub T);
+
+/// Each element of an encoded value, in descending order
+fn descending(encoded: Tuple) -> Vec<Segment> {
+    match Segment::decode(encoded.as_bytes()) {
+        Ok(segments) => segments.into_iter().map(|segment| Segment::Descending(Box::new(segment))).collect(),
+        // Tuples built from raw bytes may not decode, so are inverted as they are
+        Err(_) => vec![Segment::Descending(Box::new(Segment::Tuple(encoded.into_bytes())))],
+    }
+}
+
+/// Whether an element starting with `code` is descending - inverted type codes all have the top
+/// bit set, unlike any valid one
+pub(crate) fn is_inverted(code: u8) -> bool {
+    code & 0x80 != 0
+}
+
+/// Decode a tuple which may contain descending elements, picking them out by their inverted type
+/// codes, and failing with the error from decoding it as an ordinary tuple
+pub(crate) fn decode(input: &[u8]) -> Result<Vec<Segment>, TupleError> {
+    Segment::decode(input)
+        .or_else(|err| decode_elements(input, &DecodeOptions::default(), |_, code| is_inverted(code)).map_err(|_| err))
+}
+
+/// Decode one element at a time, reading the elements for which `descending` returns true from an
+/// inverted copy of the input
+///
+/// `descending` is given the index of each element and the byte it starts with.
+pub(crate) fn decode_elements<F: Fn(usize, u8) -> bool>(input: &[u8], options: &DecodeOptions, descending: F) -> Result<Vec<Segment>, TupleError> {
+    options.check_bytes(input.len())?;
+
+    let inverted = input.iter().map(|byte| !byte).collect::<Vec<_>>();
+    let mut state = DecodeState { options, elements: 0 };
+    let mut segments = Vec::new();
+    let mut index = 0;
+
+    while index < input.len() {
+        let (element, end) = if descending(segments.len(), input[index]) {
+            let (element, mut end) = options.decode_element(&inverted, index, &mut state)?;
+            if element.iter().any(Segment::is_byte_string) {
+                // Descending byte strings end with a second null
+                match inverted.get(end) {
+                    Some(&NULL) => end += 1,
+                    _ => return Err(TupleError::NonCanonical { position: end }),
+                }
+            }
+            (element.into_iter().map(|segment| Segment::Descending(Box::new(segment))).collect(), end)
+        } else {
+            options.decode_element(input, index, &mut state)?
+        };
+        segments.extend(element);
+        index = end;
+    }
+
+    Ok(segments)
+}
+
+impl<T> AddToTuple<Descending<T>> for Tuple where Tuple: AddToTuple<T> {
+    fn add(&mut self, v: Descending<T>) {
+        for segment in descending(Tuple::new().with(v.0)).iter() {
+            self.add_segment(segment);
+        }
+    }
+}
+
+impl<T, const N: usize> AddToTuple<Descending<T>> for ArrayTuple<N> where Tuple: AddToTuple<T> {
+    fn add(&mut self, v: Descending<T>) {
+        for segment in descending(Tuple::new().with(v.0)).iter() {
+            self.add_segment(segment);
+        }
+    }
+}
+
+impl<T: TuplePack> TuplePack for Descending<T> {
+    fn pack_into(&self, tuple: &mut Tuple) {
+        for segment in descending(self.0.pack()).iter() {
+            tuple.add_segment(segment);
+        }
+    }
+}
+
+impl<T: FromTuple> FromTuple for Descending<T> {
+    fn unpack(segments: &mut IntoIter<Segment>) -> Result<Descending<T>, TupleError> {
+        match segments.next().ok_or(TupleError::MissingElement)? {
+            Segment::Descending(inner) => T::from_segments(vec![*inner]).map(Descending),
+            other => Err(TupleError::TypeMismatch { expected: SegmentType::Descending, found: other.segment_type() }),
+        }
+    }
+}
+
+impl<E: Into<ElementSchema>> From<Descending<E>> for ElementSchema {
+    fn from(element: Descending<E>) -> ElementSchema {
+        ElementSchema::Descending(Box::new(element.0.into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::String;
+    use schema::TupleSchema;
+
+    fn sorted<T: Clone>(values: &[T]) -> Vec<T> where Tuple: AddToTuple<Descending<T>> {
+        let mut keys = values.iter()
+            .map(|value| (::tuple!(Descending(value.clone())).into_bytes(), value.clone()))
+            .collect::<Vec<_>>();
+        keys.sort_by(|a, b| a.0.cmp(&b.0));
+        keys.into_iter().map(|(_, value)| value).collect()
+    }
+
+    #[test]
+    fn test_reverses_order() {
+        assert_eq!(sorted(&[0i64, -1, 300, i64::MIN, 1, i64::MAX]), vec![i64::MAX, 300, 1, 0, -1, i64::MIN]);
+        assert_eq!(sorted(&["b", "ab", "a", "", "a\0", "ba"]), vec!["ba", "b", "ab", "a\0", "a", ""]);
+        assert_eq!(sorted(&[vec![0u8], vec![], vec![0, 0], vec![255]]), vec![vec![255], vec![0, 0], vec![0], vec![]]);
+        assert_eq!(sorted(&[1.5f64, -0.0, f64::INFINITY, -2.0]), vec![f64::INFINITY, 1.5, -0.0, -2.0]);
+    }
+
+    #[test]
+    fn test_prefix_free() {
+        // A shorter descending element must not absorb the elements which follow it
+        let short = ::tuple!(Descending("a"), 5);
+        let long = ::tuple!(Descending("ab"), 1);
+
+        assert!(long.as_bytes() < short.as_bytes());
+
+        let schema = TupleSchema::new().element(Descending(SegmentType::String)).element(SegmentType::Integer);
+        assert_eq!(schema.decode(short.as_bytes()), Ok(vec![
+            Segment::Descending(Box::new(Segment::String(String::from("a")))),
+            Segment::Integer(5),
+        ]));
+    }
+
+    #[test]
+    fn test_pack_unpack() {
+        let key = (String::from("posts"), Descending(String::from("zebra")), Descending(7i64));
+        let packed = key.pack();
+
+        assert_eq!(packed.as_bytes(), ::tuple!("posts", Descending("zebra"), Descending(7)).as_bytes());
+        assert_eq!(ArrayTuple::<32>::new().with("posts").with(Descending("zebra")).with(Descending(7)).as_bytes(), packed.as_bytes());
+
+        let schema = TupleSchema::new()
+            .element(SegmentType::String)
+            .element(Descending(SegmentType::String))
+            .element(Descending(SegmentType::Integer));
+        let segments = schema.decode(packed.as_bytes()).unwrap();
+
+        assert_eq!(FromTuple::from_segments(segments), Ok(key));
+    }
+
+    #[test]
+    fn test_decode_without_schema() {
+        let key = ::tuple!("posts", Descending(5), Descending("ab"), 1);
+
+        assert_eq!(decode(key.as_bytes()), Ok(vec![
+            Segment::String(String::from("posts")),
+            Segment::Descending(Box::new(Segment::Integer(5))),
+            Segment::Descending(Box::new(Segment::String(String::from("ab")))),
+            Segment::Integer(1),
+        ]));
+        assert_eq!(decode(&[0x99]), Err(TupleError::DecodeError { position: 0, type_code: 0x99 }));
+        assert_eq!(decode(&key.as_bytes()[..10]), Segment::decode(&key.as_bytes()[..10]));
+    }
+
+    #[test]
+    fn test_unpack_mismatch() {
+        let segments = ::tuple!(7).as_segments().unwrap();
+
+        assert_eq!(Descending::<i64>::from_segments(segments), Err(TupleError::TypeMismatch {
+            expected: SegmentType::Descending,
+            found: SegmentType::Integer,
+        }));
+    }
+}
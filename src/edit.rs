@@ -0,0 +1,268 @@
+//! Editing the elements of an encoded tuple in place
+//!
+//! Elements are located by walking over their encoded boundaries, so only the elements being
+//! changed are ever decoded or encoded.
+
+use alloc::vec::Vec;
+use decode::DecodeOptions;
+use errors::TupleError;
+use segment::Segment;
+use Tuple;
+
+impl Tuple {
+    /// Append a segment to the end of this tuple
+    pub fn push(&mut self, segment: &Segment) {
+        self.add_segment(segment);
+    }
+
+    /// Remove and return the last element of this tuple, or `None` if it is empty
+    pub fn pop(&mut self) -> Result<Option<Segment>, TupleError> {
+        let index = self.index()?;
+        let start = match index.len() {
+            0 => return Ok(None),
+            len => index.offset(len - 1).unwrap(),
+        };
+
+        let segment = decode_element(&self.buffer[start..])?;
+        self.buffer.truncate(start);
+
+        Ok(Some(segment))
+    }
+
+    /// Keep the first `len` elements of this tuple, removing the rest
+    ///
+    /// Has no effect if the tuple has `len` or fewer elements.
+    pub fn truncate(&mut self, len: usize) -> Result<(), TupleError> {
+        match self.offset_of(len) {
+            Ok(offset) => {
+                self.buffer.truncate(offset);
+                Ok(())
+            }
+            Err(TupleError::IndexOutOfBounds { .. }) => Ok(()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Insert a segment at position `index`, shifting the following elements along
+    ///
+    /// Fails with `TupleError::IndexOutOfBounds` if `index` is greater than the number of elements,
+    /// or `TupleError::NestedDescending` if a nested tuple within the segment contains a descending
+    /// element.
+    ///
+    /// # Examples
+    /// ```
+    /// #[macro_use] extern crate binary_tuples;
+    ///
+    /// use binary_tuples::segment::Segment;
+    ///
+    /// # fn main() {
+    /// let mut tuple = tuple!("users", "posts");
+    /// tuple.insert(1, &Segment::Integer(1)).unwrap();
+    ///
+    /// assert_eq!(tuple.as_bytes(), tuple!("users", 1, "posts").as_bytes());
+    /// # }
+    /// ```
+    pub fn insert(&mut self, index: usize, segment: &Segment) -> Result<(), TupleError> {
+        segment.check_nesting()?;
+        let offset = self.offset_of(index)?;
+
+        let encoded = encode(segment);
+        self.buffer.splice(offset..offset, encoded);
+
+        Ok(())
+    }
+
+    /// Replace the element at position `index`, returning the element which was replaced
+    ///
+    /// Fails with `TupleError::IndexOutOfBounds` if there is no element at `index`, or
+    /// `TupleError::NestedDescending` as `insert` does.
+    pub fn replace(&mut self, index: usize, segment: &Segment) -> Result<Segment, TupleError> {
+        segment.check_nesting()?;
+        let start = self.offset_of(index)?;
+        if start == self.buffer.len() {
+            return Err(TupleError::IndexOutOfBounds { index, len: index });
+        }
+        let end = DecodeOptions::default().find_element_end(&self.buffer, start)?;
+
+        let replaced = decode_element(&self.buffer[start..end])?;
+        let encoded = encode(segment);
+        self.buffer.splice(start..end, encoded);
+
+        Ok(replaced)
+    }
+
+    /// Remove and return the element at position `index`, shifting the following elements back
+    ///
+    /// Fails with `TupleError::IndexOutOfBounds` if there is no element at `index`.
+    pub fn remove(&mut self, index: usize) -> Result<Segment, TupleError> {
+        let start = self.offset_of(index)?;
+        if start == self.buffer.len() {
+            return Err(TupleError::IndexOutOfBounds { index, len: index });
+        }
+        let end = DecodeOptions::default().find_element_end(&self.buffer, start)?;
+
+        let removed = decode_element(&self.buffer[start..end])?;
+        self.buffer.drain(start..end);
+
+        Ok(removed)
+    }
+
+    /// Split this tuple into the elements before `index` and the elements from `index` onwards
+    ///
+    /// Fails with `TupleError::IndexOutOfBounds` if `index` is greater than the number of elements.
+    ///
+    /// # Examples
+    /// ```
+    /// #[macro_use] extern crate binary_tuples;
+    ///
+    /// # fn main() {
+    /// let (prefix, suffix) = tuple!("users", 1, "posts", 2).split_at(2).unwrap();
+    ///
+    /// assert_eq!(prefix.as_bytes(), tuple!("users", 1).as_bytes());
+    /// assert_eq!(suffix.as_bytes(), tuple!("posts", 2).as_bytes());
+    /// # }
+    /// ```
+    pub fn split_at(&self, index: usize) -> Result<(Tuple, Tuple), TupleError> {
+        let offset = self.offset_of(index)?;
+        let (prefix, suffix) = self.buffer.split_at(offset);
+
+        Ok((Tuple::from_bytes(prefix), Tuple::from_bytes(suffix)))
+    }
+
+    /// Create a new tuple containing the elements of this tuple followed by the elements of another
+    pub fn concat(&self, other: &Tuple) -> Tuple {
+        let mut buffer = Vec::with_capacity(self.buffer.len() + other.buffer.len());
+        buffer.extend_from_slice(&self.buffer);
+        buffer.extend_from_slice(&other.buffer);
+
+        Tuple { buffer }
+    }
+
+    /// The position of the element at `index`, or the end of the buffer if `index` is the number
+    /// of elements
+    fn offset_of(&self, index: usize) -> Result<usize, TupleError> {
+        let options = DecodeOptions::default();
+
+        let mut offset = 0;
+        for len in 0..index {
+            if offset == self.buffer.len() {
+                return Err(TupleError::IndexOutOfBounds { index, len });
+            }
+            offset = options.find_element_end(&self.buffer, offset)?;
+        }
+
+        Ok(offset)
+    }
+}
+
+fn encode(segment: &Segment) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(segment.encoded_len());
+    segment.encode(&mut encoded);
+    encoded
+}
+
+fn decode_element(input: &[u8]) -> Result<Segment, TupleError> {
+    Segment::decode(input).map(|mut segments| segments.remove(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::boxed::Box;
+    use alloc::string::String;
+
+    fn wide() -> Tuple {
+        ::tuple!("users", 1, vec![0, 1], -5, "posts")
+    }
+
+    #[test]
+    fn test_push_pop() {
+        let mut tuple = wide();
+        tuple.push(&Segment::Boolean(true));
+
+        assert_eq!(tuple.pop(), Ok(Some(Segment::Boolean(true))));
+        assert_eq!(tuple.pop(), Ok(Some(Segment::String(String::from("posts")))));
+        assert_eq!(tuple.as_bytes(), ::tuple!("users", 1, vec![0, 1], -5).as_bytes());
+        assert_eq!(Tuple::new().pop(), Ok(None));
+    }
+
+    #[test]
+    fn test_truncate() {
+        let mut tuple = wide();
+
+        tuple.truncate(10).unwrap();
+        assert_eq!(tuple.as_bytes(), wide().as_bytes());
+
+        tuple.truncate(3).unwrap();
+        assert_eq!(tuple.as_bytes(), ::tuple!("users", 1, vec![0, 1]).as_bytes());
+
+        tuple.truncate(0).unwrap();
+        assert!(tuple.as_bytes().is_empty());
+    }
+
+    #[test]
+    fn test_insert() {
+        let mut tuple = wide();
+
+        tuple.insert(0, &Segment::Integer(0)).unwrap();
+        tuple.insert(6, &Segment::Integer(6)).unwrap();
+        tuple.insert(3, &Segment::Nested(vec![])).unwrap();
+
+        assert_eq!(tuple.as_bytes(), ::tuple!(0, "users", 1, Vec::<Segment>::new(), vec![0, 1], -5, "posts", 6).as_bytes());
+        assert_eq!(tuple.insert(9, &Segment::Integer(9)), Err(TupleError::IndexOutOfBounds { index: 9, len: 8 }));
+
+        let nested = Segment::Nested(vec![Segment::Descending(Box::new(Segment::Integer(1)))]);
+        assert_eq!(tuple.insert(0, &nested), Err(TupleError::NestedDescending));
+        assert_eq!(tuple.replace(0, &nested), Err(TupleError::NestedDescending));
+        assert_eq!(tuple.as_bytes(), ::tuple!(0, "users", 1, Vec::<Segment>::new(), vec![0, 1], -5, "posts", 6).as_bytes());
+    }
+
+    #[test]
+    fn test_replace() {
+        let mut tuple = wide();
+
+        assert_eq!(tuple.replace(2, &Segment::Const("bytes")), Ok(Segment::Bytes(vec![0, 1])));
+        assert_eq!(tuple.replace(4, &Segment::Integer(1 << 40)), Ok(Segment::String(String::from("posts"))));
+        assert_eq!(tuple.as_bytes(), ::tuple!("users", 1, "bytes", -5, 1i64 << 40).as_bytes());
+        assert_eq!(tuple.replace(5, &Segment::Integer(0)), Err(TupleError::IndexOutOfBounds { index: 5, len: 5 }));
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut tuple = wide();
+
+        assert_eq!(tuple.remove(2), Ok(Segment::Bytes(vec![0, 1])));
+        assert_eq!(tuple.remove(3), Ok(Segment::String(String::from("posts"))));
+        assert_eq!(tuple.as_bytes(), ::tuple!("users", 1, -5).as_bytes());
+        assert_eq!(tuple.remove(3), Err(TupleError::IndexOutOfBounds { index: 3, len: 3 }));
+    }
+
+    #[test]
+    fn test_split_at() {
+        let (prefix, suffix) = wide().split_at(0).unwrap();
+        assert!(prefix.as_bytes().is_empty());
+        assert_eq!(suffix.as_bytes(), wide().as_bytes());
+
+        let (prefix, suffix) = wide().split_at(5).unwrap();
+        assert_eq!(prefix.as_bytes(), wide().as_bytes());
+        assert!(suffix.as_bytes().is_empty());
+
+        assert_eq!(wide().split_at(6).err(), Some(TupleError::IndexOutOfBounds { index: 6, len: 5 }));
+    }
+
+    #[test]
+    fn test_concat() {
+        let (prefix, suffix) = wide().split_at(2).unwrap();
+
+        assert_eq!(prefix.concat(&suffix).as_bytes(), wide().as_bytes());
+    }
+
+    #[test]
+    fn test_invalid_tuple() {
+        let mut tuple = Tuple::from_bytes(&[0x15, 1, 0x99]);
+
+        assert_eq!(tuple.insert(2, &Segment::Integer(0)), Err(TupleError::DecodeError { position: 2, type_code: 0x99 }));
+        assert_eq!(tuple.pop(), Err(TupleError::DecodeError { position: 2, type_code: 0x99 }));
+        assert_eq!(tuple.as_bytes(), &[0x15, 1, 0x99]);
+    }
+}
@@ -1,4 +1,12 @@
-use std::string::FromUtf8Error;
+use alloc::string::FromUtf8Error;
+use alloc::vec::Vec;
+use core::fmt;
+use decode::Limit;
+use segment::SegmentType;
+#[cfg(feature = "std")]
+use std::error::Error;
+#[cfg(feature = "std")]
+use std::io;
 
 #[derive(Debug, PartialEq)]
 pub enum TupleError {
@@ -9,10 +17,107 @@ pub enum TupleError {
     IntegerDecodeError{ position: usize },
     DecimalDecodeError { position: usize },
     UuidDecodeError { position: usize },
+    ParseError { position: usize },
+    JsonDecodeError,
+    BufferTooSmall { required: usize },
+    LimitExceeded { limit: Limit, position: usize },
+    NonCanonical { position: usize },
+    IndexOutOfBounds { index: usize, len: usize },
+    TypeMismatch { expected: SegmentType, found: SegmentType },
+    MissingElement,
+    TrailingElements { count: usize },
+    PrefixMismatch,
+    SchemaMismatch { path: Vec<usize>, expected: SegmentType, found: SegmentType },
+    ElementCountMismatch { path: Vec<usize>, min: usize, max: Option<usize>, found: usize },
+    ConversionFailed { index: usize, from: SegmentType, to: SegmentType },
+    NestedDescending,
+}
+
+/// The position of a (possibly nested) element, such as `3.1`
+struct ElementPath<'a>(&'a [usize]);
+
+impl<'a> fmt::Display for ElementPath<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, index) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, ".")?;
+            }
+            write!(f, "{}", index)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The number of elements allowed by a schema
+struct ElementCount(usize, Option<usize>);
+
+impl fmt::Display for ElementCount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ElementCount(min, Some(max)) if min == max => write!(f, "{}", min),
+            ElementCount(min, Some(max)) => write!(f, "{} to {}", min, max),
+            ElementCount(min, None) => write!(f, "at least {}", min),
+        }
+    }
+}
+
+impl fmt::Display for TupleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TupleError::DecodeError { position, type_code } =>
+                write!(f, "unknown type code 0x{:02x} at position {}", type_code, position),
+            TupleError::TruncatedNestedTuple => write!(f, "nested tuple is not terminated"),
+            TupleError::TruncatedTuple => write!(f, "tuple ended before the end of the input"),
+            TupleError::StringDecodeError => write!(f, "string is not valid utf-8"),
+            TupleError::IntegerDecodeError { position } =>
+                write!(f, "truncated integer at position {}", position),
+            TupleError::DecimalDecodeError { position } =>
+                write!(f, "truncated floating point number at position {}", position),
+            TupleError::UuidDecodeError { position } =>
+                write!(f, "invalid uuid at position {}", position),
+            TupleError::ParseError { position } =>
+                write!(f, "invalid tuple syntax at position {}", position),
+            TupleError::JsonDecodeError => write!(f, "invalid json tuple representation"),
+            TupleError::BufferTooSmall { required } =>
+                write!(f, "buffer is too small, {} bytes are required", required),
+            TupleError::LimitExceeded { limit, position } =>
+                write!(f, "{} limit exceeded at position {}", limit, position),
+            TupleError::NonCanonical { position } =>
+                write!(f, "non canonical encoding at position {}", position),
+            TupleError::IndexOutOfBounds { index, len } =>
+                write!(f, "index {} is out of bounds for a tuple of {} elements", index, len),
+            TupleError::TypeMismatch { expected, found } =>
+                write!(f, "expected {} element but found {}", expected, found),
+            TupleError::MissingElement => write!(f, "tuple has too few elements"),
+            TupleError::TrailingElements { count } =>
+                write!(f, "tuple has {} unexpected trailing elements", count),
+            TupleError::PrefixMismatch => write!(f, "tuple does not start with the expected prefix"),
+            TupleError::SchemaMismatch { path, expected, found } =>
+                write!(f, "element {} expected {}, found {}", ElementPath(path), expected, found),
+            TupleError::ElementCountMismatch { path, min, max, found } if path.is_empty() =>
+                write!(f, "tuple has {} elements, expected {}", found, ElementCount(*min, *max)),
+            TupleError::ElementCountMismatch { path, min, max, found } =>
+                write!(f, "nested tuple at element {} has {} elements, expected {}", ElementPath(path), found, ElementCount(*min, *max)),
+            TupleError::ConversionFailed { index, from, to } =>
+                write!(f, "element {} can't be converted from {} to {}", index, from, to),
+            TupleError::NestedDescending => write!(f, "descending elements can't be nested inside a tuple"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for TupleError {}
+
+#[cfg(feature = "std")]
+impl From<TupleError> for io::Error {
+    fn from(err: TupleError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, err)
+    }
 }
 
 impl From<FromUtf8Error> for TupleError {
     fn from(_err: FromUtf8Error) -> Self {
         TupleError::StringDecodeError
     }
-}
\ No newline at end of file
+}
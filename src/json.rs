@@ -0,0 +1,308 @@
+//! Conversion between tuples and JSON values
+//!
+//! Tuples are represented as JSON arrays. Every element maps to a single JSON value so that the
+//! original encoded bytes can always be recovered:
+//!
+//! | Segment                           | JSON                                        |
+//! |-----------------------------------|---------------------------------------------|
+//! | `String`                          | `"users"`                                   |
+//! | `Integer` within ±2<sup>53</sup>  | `42`                                        |
+//! | `Integer` beyond ±2<sup>53</sup>  | `{"integer": "9007199254740993"}`           |
+//! | `Double`                          | `{"double": 1.5}`, `{"double": "NaN"}`      |
+//! | `Float`                           | `{"float": 1.5}`, `{"float": "-Infinity"}`  |
+//! | `Boolean`                         | `true`                                      |
+//! | `Bytes`                           | `{"bytes": "AQID"}` (standard base64)       |
+//! | `UUID`                            | `{"uuid": "c5c2a280-e47c-4181-94b3-c23cd5faede8"}` |
+//! | `Nested`                          | `["nested", 1]`                             |
+//! | `Descending`                      | `{"descending": "users"}`                   |
+//!
+//! NaNs other than the standard quiet NaN keep their exact bits, such as
+//! `{"double": {"bits": "0x7ff8000000000001"}}`, as they are encoded (and sort) differently.
+//!
+//! Plain JSON numbers with a fractional part are accepted as doubles when converting from JSON, but
+//! are never produced - integer valued doubles would otherwise become integers after passing
+//! through most JSON libraries.
+
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde_json::{Map, Number, Value};
+use errors::TupleError;
+use segment::Segment;
+use text::{parse_uuid, write_uuid};
+
+const MAX_SAFE_INTEGER: i64 = 1 << 53;
+
+/// Convert a list of segments into a JSON array
+///
+/// # Examples
+/// ```
+/// extern crate binary_tuples;
+/// #[macro_use] extern crate serde_json;
+///
+/// use binary_tuples::{json, segment::Segment};
+///
+/// # fn main() {
+/// let value = json::to_json(&[Segment::Const("users"), Segment::Bytes(vec![1, 2, 3])]).unwrap();
+///
+/// assert_eq!(value, json!(["users", {"bytes": "AQID"}]));
+/// # }
+/// ```
+pub fn to_json(segments: &[Segment]) -> Result<Value, TupleError> {
+    let mut values = Vec::with_capacity(segments.len());
+    append_json(segments, &mut values)?;
+
+    Ok(Value::Array(values))
+}
+
+/// Convert a JSON array produced by `to_json` back into a list of segments
+pub fn from_json(value: &Value) -> Result<Vec<Segment>, TupleError> {
+    match value {
+        Value::Array(values) => values.iter().map(segment_from_json).collect(),
+        _ => Err(TupleError::JsonDecodeError)
+    }
+}
+
+fn append_json(segments: &[Segment], values: &mut Vec<Value>) -> Result<(), TupleError> {
+    for segment in segments {
+        let value = match segment {
+            Segment::Bytes(data) => tagged("bytes", Value::String(STANDARD.encode(data))),
+            Segment::String(data) => Value::String(data.clone()),
+            Segment::Const(data) => Value::String(String::from(*data)),
+            Segment::Nested(inner) => to_json(inner)?,
+            Segment::Integer(value) if (-MAX_SAFE_INTEGER..=MAX_SAFE_INTEGER).contains(value) => {
+                Value::Number(Number::from(*value))
+            }
+            Segment::Integer(value) => tagged("integer", Value::String(value.to_string())),
+            Segment::Float(value) if value.is_nan() && value.to_bits() != f32::NAN.to_bits() => {
+                tagged("float", tagged("bits", Value::String(format!("0x{:08x}", value.to_bits()))))
+            }
+            Segment::Float(value) => tagged("float", float_to_json(f64::from(*value))),
+            Segment::Double(value) if value.is_nan() && value.to_bits() != f64::NAN.to_bits() => {
+                tagged("double", tagged("bits", Value::String(format!("0x{:016x}", value.to_bits()))))
+            }
+            Segment::Double(value) => tagged("double", float_to_json(*value)),
+            Segment::Boolean(value) => Value::Bool(*value),
+            Segment::UUID(value) => {
+                let mut uuid = String::with_capacity(36);
+                write_uuid(&mut uuid, value).unwrap();
+                tagged("uuid", Value::String(uuid))
+            }
+            Segment::Tuple(value) => {
+                append_json(&Segment::decode(value)?, values)?;
+                continue;
+            }
+            Segment::Descending(inner) => {
+                let mut inner_values = Vec::with_capacity(1);
+                append_json(core::slice::from_ref(&**inner), &mut inner_values)?;
+                match inner_values.pop() {
+                    Some(inner) if inner_values.is_empty() => tagged("descending", inner),
+                    _ => return Err(TupleError::JsonDecodeError),
+                }
+            }
+        };
+
+        values.push(value);
+    }
+
+    Ok(())
+}
+
+fn tagged(tag: &str, value: Value) -> Value {
+    let mut object = Map::new();
+    object.insert(String::from(tag), value);
+
+    Value::Object(object)
+}
+
+fn float_to_json(value: f64) -> Value {
+    match Number::from_f64(value) {
+        Some(number) => Value::Number(number),
+        None if value.is_nan() => Value::String(String::from("NaN")),
+        None if value > 0.0 => Value::String(String::from("Infinity")),
+        None => Value::String(String::from("-Infinity")),
+    }
+}
+
+fn float_from_json(value: &Value) -> Result<f64, TupleError> {
+    match value {
+        Value::Number(number) => number.as_f64().ok_or(TupleError::JsonDecodeError),
+        Value::String(name) if name == "NaN" => Ok(f64::NAN),
+        Value::String(name) if name == "Infinity" => Ok(f64::INFINITY),
+        Value::String(name) if name == "-Infinity" => Ok(f64::NEG_INFINITY),
+        _ => Err(TupleError::JsonDecodeError)
+    }
+}
+
+/// The raw bits of a NaN written as `{"bits": "0x..."}`
+fn bits_from_json(value: &Value) -> Option<u64> {
+    match value {
+        Value::Object(object) if object.len() == 1 => match object.get("bits") {
+            Some(Value::String(bits)) => bits.strip_prefix("0x").and_then(|hex| u64::from_str_radix(hex, 16).ok()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn double_from_json(value: &Value) -> Result<f64, TupleError> {
+    match value {
+        Value::Object(_) => bits_from_json(value).map(f64::from_bits).ok_or(TupleError::JsonDecodeError),
+        _ => float_from_json(value),
+    }
+}
+
+fn single_from_json(value: &Value) -> Result<f32, TupleError> {
+    match value {
+        Value::Object(_) => bits_from_json(value)
+            .and_then(|bits| u32::try_from(bits).ok())
+            .map(f32::from_bits)
+            .ok_or(TupleError::JsonDecodeError),
+        Value::String(name) if name == "NaN" => Ok(f32::NAN),
+        _ => Ok(float_from_json(value)? as f32),
+    }
+}
+
+fn segment_from_json(value: &Value) -> Result<Segment, TupleError> {
+    match value {
+        Value::String(data) => Ok(Segment::String(data.clone())),
+        Value::Bool(value) => Ok(Segment::Boolean(*value)),
+        Value::Array(_) => {
+            let inner = from_json(value)?;
+            // Descending elements can't be encoded inside nested tuples
+            if inner.iter().any(|segment| matches!(segment, Segment::Descending(_))) {
+                return Err(TupleError::JsonDecodeError);
+            }
+            Ok(Segment::Nested(inner))
+        }
+        Value::Number(number) => match number.as_i64() {
+            Some(value) if (-MAX_SAFE_INTEGER..=MAX_SAFE_INTEGER).contains(&value) => Ok(Segment::Integer(value)),
+            Some(_) => Err(TupleError::JsonDecodeError),
+            None if number.is_f64() => Ok(Segment::Double(number.as_f64().unwrap())),
+            None => Err(TupleError::JsonDecodeError),
+        },
+        Value::Object(object) if object.len() == 1 => {
+            let (tag, value) = object.iter().next().unwrap();
+
+            match (tag.as_str(), value) {
+                ("bytes", Value::String(data)) => STANDARD.decode(data)
+                    .map(Segment::Bytes)
+                    .map_err(|_| TupleError::JsonDecodeError),
+                ("integer", Value::String(data)) => data.parse()
+                    .map(Segment::Integer)
+                    .map_err(|_| TupleError::JsonDecodeError),
+                ("uuid", Value::String(data)) => parse_uuid(data)
+                    .map(Segment::UUID)
+                    .ok_or(TupleError::JsonDecodeError),
+                ("double", value) => Ok(Segment::Double(double_from_json(value)?)),
+                ("float", value) => Ok(Segment::Float(single_from_json(value)?)),
+                ("descending", value) => Ok(Segment::Descending(Box::new(segment_from_json(value)?))),
+                _ => Err(TupleError::JsonDecodeError)
+            }
+        }
+        _ => Err(TupleError::JsonDecodeError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Tuple;
+
+    fn round_trip(segments: Vec<Segment>) {
+        let mut expected = Tuple::new();
+        for segment in segments.iter() {
+            expected.add_segment(segment);
+        }
+
+        let value = to_json(&segments).unwrap();
+        let text = serde_json::to_string(&value).unwrap();
+        let parsed = serde_json::from_str::<Value>(&text).unwrap();
+
+        let mut result = Tuple::new();
+        for segment in from_json(&parsed).unwrap().iter() {
+            result.add_segment(segment);
+        }
+
+        assert_eq!(result.into_bytes(), expected.into_bytes());
+    }
+
+    #[test]
+    fn test_to_json() {
+        let uuid = [197, 194, 162, 128, 228, 124, 65, 129, 148, 179, 194, 60, 213, 250, 237, 232];
+        let value = to_json(&[
+            Segment::Const("users"),
+            Segment::Integer(1),
+            Segment::Integer(i64::MAX),
+            Segment::Double(2.0),
+            Segment::Float(f32::NAN),
+            Segment::Boolean(true),
+            Segment::Bytes(vec![0, 1, 2]),
+            Segment::UUID(uuid),
+            Segment::Nested(vec![Segment::Integer(-1)]),
+        ]).unwrap();
+
+        assert_eq!(serde_json::to_string(&value).unwrap(), concat!(
+            r#"["users",1,{"integer":"9223372036854775807"},{"double":2.0},{"float":"NaN"},true,"#,
+            r#"{"bytes":"AAEC"},{"uuid":"c5c2a280-e47c-4181-94b3-c23cd5faede8"},[-1]]"#
+        ));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        round_trip(vec![
+            Segment::Const("users"),
+            Segment::Integer(MAX_SAFE_INTEGER + 1),
+            Segment::Integer(i64::MIN),
+            Segment::Double(-0.0),
+            Segment::Double(f64::NEG_INFINITY),
+            Segment::Float(31415.514),
+            Segment::Bytes(vec![0, 255, 0]),
+            Segment::Nested(vec![Segment::Nested(vec![]), Segment::Boolean(false)]),
+            Segment::Descending(Box::new(Segment::Bytes(vec![1]))),
+        ]);
+    }
+
+    #[test]
+    fn test_embedded_tuple() {
+        let value = to_json(&[Segment::Tuple(vec![21, 1, 39]), Segment::Integer(2)]).unwrap();
+
+        assert_eq!(serde_json::to_string(&value).unwrap(), "[1,true,2]");
+    }
+
+    #[test]
+    fn test_nan_payloads() {
+        let value = to_json(&[Segment::Double(f64::NAN), Segment::Double(-f64::NAN), Segment::Float(f32::from_bits(0xffc0_0001))]).unwrap();
+
+        assert_eq!(serde_json::to_string(&value).unwrap(), concat!(
+            r#"[{"double":"NaN"},{"double":{"bits":"0xfff8000000000000"}},"#,
+            r#"{"float":{"bits":"0xffc00001"}}]"#
+        ));
+
+        round_trip(vec![
+            Segment::Double(f64::from_bits(0x7ff8_0000_0000_0001)),
+            Segment::Double(-f64::NAN),
+            Segment::Float(f32::from_bits(0x7f80_0001)),
+            Segment::Float(f32::NAN),
+        ]);
+    }
+
+    #[test]
+    fn test_plain_double() {
+        let value = serde_json::from_str::<Value>("[1.5]").unwrap();
+
+        assert_eq!(from_json(&value).unwrap(), vec![Segment::Double(1.5)]);
+    }
+
+    #[test]
+    fn test_invalid_json() {
+        for input in &["{}", "[null]", "[{\"bytes\": 1}]", "[{\"uuid\": \"nope\"}]", "[{\"a\": 1, \"b\": 2}]", "[9007199254740993]", "[{\"float\": {\"bits\": \"0x100000000\"}}]", "[{\"double\": {\"bits\": \"nope\"}}]", "[[{\"descending\": 1}]]"] {
+            let value = serde_json::from_str::<Value>(input).unwrap();
+
+            assert_eq!(from_json(&value), Err(TupleError::JsonDecodeError));
+        }
+    }
+}
@@ -1,26 +1,96 @@
+//! Ordered binary tuples, compatible with the FoundationDB tuple layer
+//!
+//! The crate is `no_std` and only needs an allocator - disable the default features and enable
+//! `alloc` to use it without the standard library. The `std` feature (enabled by default) adds the
+//! `std::error::Error` and `std::io` integrations, and the `uuid` feature adds `AddToTuple`
+//! implementations for `uuid::Uuid`. The `sled` and `lmdb` features add `store::KeyValueStore`
+//! adapters for those embedded stores.
+
+#![no_std]
+
+#[cfg(not(feature = "alloc"))]
+compile_error!("binary_tuples requires either the `std` or the `alloc` feature");
+
+#[cfg(feature = "std")]
+extern crate std;
+#[macro_use]
+extern crate alloc;
 extern crate byteorder;
+extern crate memchr;
+#[cfg(feature = "uuid")]
 extern crate uuid;
+#[cfg(feature = "json")]
+extern crate serde_json;
+#[cfg(feature = "json")]
+extern crate base64;
+#[cfg(feature = "bytes")]
+extern crate bytes;
+#[cfg(feature = "sled")]
+extern crate sled;
+#[cfg(feature = "lmdb")]
+extern crate heed;
 
 pub mod segment;
+pub mod decode;
+pub mod tuple_index;
+pub mod tuple_ref;
+pub mod array_tuple;
+pub mod text;
+pub mod printable;
+pub mod store;
+pub mod secondary_index;
+pub mod schema;
+pub mod migration;
+pub mod descending;
+#[cfg(feature = "std")]
+pub mod stream;
+#[cfg(feature = "json")]
+pub mod json;
+mod pack;
 mod constants;
+mod edit;
+mod prefix;
 mod utils;
 mod errors;
 
+use alloc::borrow::Borrow;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+#[cfg(feature = "uuid")]
 use uuid::Uuid;
 use segment::Segment;
-use errors::TupleError;
-
-#[derive(Clone)]
+pub use errors::TupleError;
+pub use decode::{DecodeOptions, Limit, Unknown};
+pub use tuple_index::TupleIndex;
+pub use tuple_ref::TupleRef;
+pub use array_tuple::ArrayTuple;
+pub use pack::{FromTuple, TuplePack};
+pub use schema::TupleSchema;
+pub use descending::Descending;
+#[cfg(feature = "std")]
+pub use stream::{TupleWriter, TupleDecoder};
+
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 /// A builder for serialized tuples
+///
+/// Tuples are compared, ordered and hashed by their encoded bytes, which matches the order of the
+/// keys in a FoundationDB (or any other ordered key value) store.
+///
+/// `Tuple` deliberately doesn't implement `Deref<Target = [u8]>`. Its methods work on whole
+/// elements (`starts_with` and `strip_prefix` compare elements, not bytes), and a deref would mix
+/// in the byte level slice methods - `tuple.len()` would count bytes and `tuple.ends_with(..)`
+/// would match part of an element. Use `as_bytes` or `AsRef` for the encoded bytes instead.
 pub struct Tuple {
     buffer: Vec<u8>
 }
 
 impl Tuple {
-    /// Create a new tuple
+    /// Create a new tuple, without allocating until the first segment is added
     pub fn new() -> Tuple {
         Tuple {
-            buffer: Vec::with_capacity(128)
+            buffer: Vec::new()
         }
     }
 
@@ -31,6 +101,21 @@ impl Tuple {
         }
     }
 
+    /// Create a new tuple from a list of segments
+    ///
+    /// The backing buffer is allocated with exactly the capacity required by the encoded segments.
+    ///
+    /// # Panics
+    /// If a nested tuple within the segments contains a descending element.
+    pub fn from_segments(segments: &[Segment]) -> Tuple {
+        let mut tuple = Tuple::with_capacity(segment::encoded_len(segments));
+        for segment in segments.iter() {
+            tuple.add_segment(segment);
+        }
+
+        tuple
+    }
+
     /// Create a new tuple from an existing byte array
     ///
     /// This can be used with `as_segments` to parse an existing tuple into a list of segments
@@ -57,10 +142,34 @@ impl Tuple {
     ///
     /// ## Notes
     /// It is recommended to import AddToTuple as it greatly simplifies this API
+    ///
+    /// # Panics
+    /// If a nested tuple within the segment contains a descending element, as its inverted bytes
+    /// could end the nested tuple early (see `try_add_segment`).
     pub fn add_segment(&mut self, input: &Segment) {
         input.encode(&mut self.buffer);
     }
 
+    /// Add an individual segment to this tuple, failing with `TupleError::NestedDescending` and
+    /// leaving the tuple unchanged if a nested tuple within it contains a descending element
+    ///
+    /// # Examples
+    /// ```
+    /// use binary_tuples::{Tuple, TupleError, segment::Segment};
+    ///
+    /// let mut tuple = Tuple::new();
+    /// let nested = Segment::Nested(vec![Segment::Descending(Box::new(Segment::Integer(1)))]);
+    ///
+    /// assert_eq!(tuple.try_add_segment(&nested), Err(TupleError::NestedDescending));
+    /// assert!(tuple.try_add_segment(&Segment::Descending(Box::new(Segment::Integer(1)))).is_ok());
+    /// ```
+    pub fn try_add_segment(&mut self, input: &Segment) -> Result<(), TupleError> {
+        input.check_nesting()?;
+        self.add_segment(input);
+
+        Ok(())
+    }
+
     /// Directly embed the contents of another tuple builder in this builder
     ///
     /// ## Notes
@@ -79,10 +188,213 @@ impl Tuple {
         &self.buffer
     }
 
-    /// Deserialize the segments which make up this tuple
+    /// The number of bytes in the encoded tuple
+    pub fn encoded_len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Copy the encoded tuple into a fixed size buffer, returning the number of bytes written
+    ///
+    /// Fails with `TupleError::BufferTooSmall` (without writing anything) if the buffer cannot hold
+    /// the encoded tuple.
+    pub fn encode_into(&self, buffer: &mut [u8]) -> Result<usize, TupleError> {
+        let required = self.buffer.len();
+        if required > buffer.len() {
+            return Err(TupleError::BufferTooSmall { required });
+        }
+
+        buffer[..required].copy_from_slice(&self.buffer);
+        Ok(required)
+    }
+
+    /// Copy the encoded tuple into a `bytes::BufMut`
+    #[cfg(feature = "bytes")]
+    pub fn encode_buf<B: bytes::BufMut>(&self, buffer: &mut B) {
+        buffer.put_slice(&self.buffer)
+    }
+
+    /// Borrow this tuple as a `TupleRef`
+    pub fn as_tuple_ref<'a>(&'a self) -> TupleRef<'a> {
+        TupleRef::new(&self.buffer)
+    }
+
+    /// Iterate over the decoded elements of this tuple (see `TupleRef::iter`)
+    pub fn iter<'a>(&'a self) -> tuple_ref::Iter<'a> {
+        self.as_tuple_ref().iter()
+    }
+
+    /// Deserialize the segments which make up this tuple, using the default `DecodeOptions`
     pub fn as_segments(&self) -> Result<Vec<Segment>, TupleError> {
         Segment::decode(&self.buffer)
     }
+
+    /// Deserialize the segments which make up this tuple, failing if any of the given limits are
+    /// exceeded
+    pub fn as_segments_with(&self, options: &DecodeOptions) -> Result<Vec<Segment>, TupleError> {
+        options.decode(&self.buffer)
+    }
+
+    /// Index the top level elements of this tuple for random access (see `TupleIndex`)
+    pub fn index<'a>(&'a self) -> Result<TupleIndex<'a>, TupleError> {
+        TupleIndex::new(&self.buffer)
+    }
+
+    /// Decode the tuple at the start of a key which may be followed by arbitrary bytes, returning
+    /// the decoded segments along with the number of bytes they were decoded from
+    ///
+    /// Decoding stops at the first byte which doesn't start a valid element - note that a suffix
+    /// which happens to start with a valid element will be decoded as part of the tuple, so
+    /// `decode_exact` should be used when the number of elements is known.
+    ///
+    /// # Examples
+    /// ```
+    /// use binary_tuples::{Tuple, segment::Segment};
+    ///
+    /// let (segments, read) = Tuple::decode_prefix(&[2, 117, 115, 101, 114, 115, 0, 0xFE, 0xFF]);
+    ///
+    /// assert_eq!(segments, vec![Segment::String(String::from("users"))]);
+    /// assert_eq!(read, 7);
+    /// ```
+    pub fn decode_prefix(input: &[u8]) -> (Vec<Segment>, usize) {
+        DecodeOptions::default().decode_prefix(input)
+    }
+
+    /// Decode exactly `count` elements from the start of a key, returning the decoded segments along
+    /// with the rest of the key
+    ///
+    /// # Examples
+    /// ```
+    /// use binary_tuples::{Tuple, segment::Segment};
+    ///
+    /// let (segments, rest) = Tuple::decode_exact(&[21, 1, 21, 2, 21, 3], 1).unwrap();
+    ///
+    /// assert_eq!(segments, vec![Segment::Integer(1)]);
+    /// assert_eq!(rest, &[21, 2, 21, 3]);
+    /// ```
+    pub fn decode_exact(input: &[u8], count: usize) -> Result<(Vec<Segment>, &[u8]), TupleError> {
+        DecodeOptions::default().decode_exact(input, count)
+    }
+
+    /// Deserialize as many segments as possible, returning any bytes which couldn't be decoded (see
+    /// `DecodeOptions::decode_lenient`)
+    pub fn as_segments_lenient<'a>(&'a self) -> (Vec<Segment>, Option<Unknown<'a>>) {
+        DecodeOptions::default().decode_lenient(&self.buffer)
+    }
+
+    /// Check that this tuple is canonically encoded, so that no other byte string decodes to the
+    /// same segments
+    ///
+    /// # Examples
+    /// ```
+    /// use binary_tuples::{Tuple, TupleError};
+    ///
+    /// assert!(Tuple::from_bytes(&[21, 1]).validate_canonical().is_ok());
+    /// assert_eq!(Tuple::from_bytes(&[22, 0, 1]).validate_canonical(), Err(TupleError::NonCanonical { position: 0 }));
+    /// ```
+    pub fn validate_canonical(&self) -> Result<(), TupleError> {
+        DecodeOptions::new().strict(true).decode(&self.buffer).map(|_| ())
+    }
+
+    /// Convert this tuple into its JSON representation (see the `json` module)
+    #[cfg(feature = "json")]
+    pub fn to_json(&self) -> Result<serde_json::Value, TupleError> {
+        json::to_json(&self.as_segments()?)
+    }
+
+    /// Create a tuple from its JSON representation (see the `json` module)
+    #[cfg(feature = "json")]
+    pub fn from_json(value: &serde_json::Value) -> Result<Tuple, TupleError> {
+        Ok(Tuple::from_segments(&json::from_json(value)?))
+    }
+
+    /// Return the range of keys which contain this tuple as a prefix
+    ///
+    /// The range starts at the tuple followed by `0x00` (inclusive) and finishes at the tuple followed
+    /// by `0xFF` (exclusive), matching the ranges produced by the FoundationDB client libraries.
+    ///
+    /// # Examples
+    /// ```
+    /// #[macro_use] extern crate binary_tuples;
+    ///
+    /// let (begin, end) = tuple!("users").range();
+    ///
+    /// assert_eq!(begin, vec![2, 117, 115, 101, 114, 115, 0, 0]);
+    /// assert_eq!(end, vec![2, 117, 115, 101, 114, 115, 0, 255]);
+    /// ```
+    pub fn range(&self) -> (Vec<u8>, Vec<u8>) {
+        self.as_tuple_ref().range()
+    }
+}
+
+impl FromStr for Tuple {
+    type Err = TupleError;
+
+    /// Parse a tuple from its textual representation (see the `text` module)
+    fn from_str(input: &str) -> Result<Tuple, TupleError> {
+        Ok(Tuple::from_segments(&text::parse(input)?))
+    }
+}
+
+impl Default for Tuple {
+    fn default() -> Tuple {
+        Tuple::new()
+    }
+}
+
+impl fmt::Debug for Tuple {
+    /// Format the tuple in its textual representation, or as escaped bytes if it is invalid
+    ///
+    /// Descending elements are recognised by their inverted type codes, and shown as `desc(...)`.
+    ///
+    /// # Examples
+    /// ```
+    /// #[macro_use] extern crate binary_tuples;
+    ///
+    /// use binary_tuples::{Descending, Tuple};
+    ///
+    /// # fn main() {
+    /// assert_eq!(format!("{:?}", tuple!("users", 1)), "Tuple(\"users\", 1)");
+    /// assert_eq!(format!("{:?}", Tuple::from_bytes(&[21])), "Tuple(<invalid: \\x15>)");
+    /// assert_eq!(format!("{:?}", tuple!("posts", Descending(5), Descending("ab"))), "Tuple(\"posts\", desc(5), desc(\"ab\"))");
+    /// # }
+    /// ```
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.as_tuple_ref().fmt_named(f, "Tuple")
+    }
+}
+
+impl From<Vec<u8>> for Tuple {
+    /// Wrap an encoded tuple without copying it
+    fn from(buffer: Vec<u8>) -> Tuple {
+        Tuple { buffer }
+    }
+}
+
+impl From<Tuple> for Vec<u8> {
+    fn from(tuple: Tuple) -> Vec<u8> {
+        tuple.buffer
+    }
+}
+
+impl AsRef<[u8]> for Tuple {
+    fn as_ref(&self) -> &[u8] {
+        &self.buffer
+    }
+}
+
+impl Borrow<[u8]> for Tuple {
+    fn borrow(&self) -> &[u8] {
+        &self.buffer
+    }
+}
+
+impl<'a> IntoIterator for &'a Tuple {
+    type Item = Result<Segment, TupleError>;
+    type IntoIter = tuple_ref::Iter<'a>;
+
+    fn into_iter(self) -> tuple_ref::Iter<'a> {
+        self.iter()
+    }
 }
 
 /// An extension trait to simplify working with segments
@@ -161,13 +473,31 @@ impl AddToTuple<&'static str> for Tuple {
     }
 }
 
+impl AddToTuple<[u8; 16]> for Tuple {
+    fn add(&mut self, v: [u8; 16]) {
+        self.add_segment(&Segment::UUID(v));
+    }
+}
+
+#[cfg(feature = "uuid")]
 impl AddToTuple<Uuid> for Tuple {
     fn add(&mut self, v: Uuid) {
-        self.add_segment(&Segment::UUID(v));
+        self.add_segment(&Segment::UUID(v.into_bytes()));
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl<'a> AddToTuple<&'a Uuid> for Tuple {
+    fn add(&mut self, v: &'a Uuid) {
+        self.add_segment(&Segment::UUID(*v.as_bytes()));
     }
 }
 
 impl AddToTuple<Vec<Segment>> for Tuple {
+    /// Add the segments as a nested tuple
+    ///
+    /// # Panics
+    /// If the segments contain a descending element (see `Tuple::try_add_segment`).
     fn add(&mut self, v: Vec<Segment>) {
         self.add_segment(&Segment::Nested(v));
     }
@@ -175,7 +505,7 @@ impl AddToTuple<Vec<Segment>> for Tuple {
 
 impl<'a> AddToTuple<&'a Tuple> for Tuple {
     fn add(&mut self, v: &'a Tuple) {
-        self.add_builder(&v);
+        self.add_builder(v);
     }
 }
 
@@ -201,6 +531,9 @@ impl AddToTuple<Tuple> for Tuple {
 /// let bytes = value.into_bytes();
 ///```
 ///
+/// # Panics
+/// If a nested tuple contains a descending element, as `Tuple::add_segment` does.
+///
 /// Tuples can reused as efficient prefixes for other tuples
 /// ```
 /// #[macro_use] extern crate binary_tuples;
@@ -232,6 +565,8 @@ macro_rules! tuple {
 
 #[cfg(test)]
 mod tests {
+    use alloc::vec::Vec;
+
     #[test]
     fn test_tuple_macro() {
         let result = tuple!("Test").into_bytes();
@@ -254,6 +589,108 @@ mod tests {
         assert_eq!(tuple.into_bytes(), vec![1, 1, 2, 3, 0]);
     }
 
+    #[test]
+    fn test_range() {
+        let (begin, end) = tuple!(1).range();
+
+        assert_eq!(begin, vec![21, 1, 0]);
+        assert_eq!(end, vec![21, 1, 255]);
+    }
+
+    #[test]
+    fn test_from_str() {
+        let tuple: ::Tuple = "(\"users\", 1)".parse().unwrap();
+
+        assert_eq!(tuple.into_bytes(), tuple!("users", 1).into_bytes());
+    }
+
+    #[test]
+    fn test_new_does_not_allocate() {
+        assert_eq!(::Tuple::new().buffer.capacity(), 0);
+        assert_eq!(::Tuple::with_capacity(64).buffer.capacity(), 64);
+    }
+
+    #[test]
+    fn test_from_segments() {
+        let tuple = ::Tuple::from_segments(&[::Segment::Const("Test"), ::Segment::Integer(1)]);
+
+        assert_eq!(tuple.buffer.capacity(), 8);
+        assert_eq!(tuple.into_bytes(), vec![2, 84, 101, 115, 116, 0, 21, 1]);
+    }
+
+    #[test]
+    fn test_encode_into() {
+        let tuple = tuple!("Test");
+        let mut buffer = [0; 8];
+
+        assert_eq!(tuple.encode_into(&mut buffer), Ok(6));
+        assert_eq!(&buffer[..6], tuple.as_bytes());
+        assert_eq!(tuple.encode_into(&mut buffer[..5]), Err(::TupleError::BufferTooSmall { required: 6 }));
+    }
+
+    #[test]
+    #[cfg(feature = "bytes")]
+    fn test_encode_buf() {
+        let mut buffer = ::bytes::BytesMut::new();
+        tuple!("Test").encode_buf(&mut buffer);
+        ::Segment::Integer(1).encode_buf(&mut buffer);
+
+        assert_eq!(&buffer[..], &[2, 84, 101, 115, 116, 0, 21, 1]);
+    }
+
+    #[test]
+    fn test_uuid_support() {
+        let tuple = tuple!([7; 16]);
+
+        assert_eq!(tuple.into_bytes(), vec![48, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7]);
+    }
+
+    #[test]
+    #[cfg(feature = "uuid")]
+    fn test_uuid_crate_support() {
+        let uuid = ::uuid::Uuid::from_bytes([7; 16]);
+
+        assert_eq!(tuple!(uuid).into_bytes(), tuple!(&uuid).into_bytes());
+        assert_eq!(tuple!(uuid).as_segments().unwrap(), vec![::Segment::UUID([7; 16])]);
+    }
+
+    #[test]
+    fn test_ordering() {
+        let mut tuples = vec![tuple!("b"), tuple!("a", 2), tuple!(-1), tuple!("a", 1), tuple!("a")];
+        tuples.sort();
+
+        assert_eq!(tuples, vec![tuple!("a"), tuple!("a", 1), tuple!("a", 2), tuple!("b"), tuple!(-1)]);
+        assert!(tuple!("a") != tuple!("a", 1));
+    }
+
+    #[test]
+    fn test_map_keys() {
+        use alloc::collections::BTreeMap;
+
+        let mut map = BTreeMap::new();
+        map.insert(tuple!("users", 1), "one");
+        map.insert(tuple!("users", 2), "two");
+
+        assert_eq!(map.get(&tuple!("users", 2)), Some(&"two"));
+        assert_eq!(map.get(tuple!("users", 1).as_bytes()), Some(&"one"));
+    }
+
+    #[test]
+    fn test_conversions() {
+        let tuple = ::Tuple::from(vec![21, 1]);
+
+        assert_eq!(tuple, tuple!(1));
+        assert_eq!(tuple.encoded_len(), 2);
+        assert_eq!(AsRef::<[u8]>::as_ref(&tuple), &[21, 1]);
+        assert_eq!(Vec::from(tuple), vec![21, 1]);
+    }
+
+    #[test]
+    fn test_debug() {
+        assert_eq!(format!("{:?}", tuple!("a", vec![0], 1.5)), "Tuple(\"a\", b\"\\x00\", 1.5)");
+        assert_eq!(format!("{:?}", ::Tuple::new()), "Tuple()");
+    }
+
     #[test]
     fn test_u8_support() {
         let binary: Vec<u8> = vec![1, 2, 3];
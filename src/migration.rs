@@ -0,0 +1,558 @@
+//! Rewriting keys from one layout to another
+//!
+//! A `Migration` is a list of element level edits - moving, inserting, removing and converting
+//! elements - applied to each key in turn. Keys can be checked against schemas before and after
+//! they are rewritten, and a dry run reports every change without writing anything.
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use errors::TupleError;
+use schema::TupleSchema;
+use segment::{Segment, SegmentType};
+use store::{BatchOp, KeyValueStore, StoreError};
+use {text, Tuple};
+
+/// A single edit made to every key
+#[derive(Clone, Debug, PartialEq)]
+pub enum Step {
+    /// Move the element at `from` so that it ends up at `to`
+    Move { from: usize, to: usize },
+    /// Insert a default value at `index`
+    Insert { index: usize, value: Segment },
+    /// Remove the element at `index`
+    Remove { index: usize },
+    /// Convert the element at `index` to another type (see `convert`)
+    Convert { index: usize, to: SegmentType },
+}
+
+/// A set of edits which rewrite keys from one layout to another
+///
+/// # Examples
+/// ```
+/// #[macro_use] extern crate binary_tuples;
+///
+/// use binary_tuples::migration::Migration;
+/// use binary_tuples::segment::{Segment, SegmentType};
+/// use binary_tuples::store::{KeyValueStore, MemoryStore};
+///
+/// # fn main() {
+/// // ("users", id as a string, region) -> ("users", region, id as a uuid, version)
+/// let migration = Migration::new()
+///     .move_element(2, 1)
+///     .convert(2, SegmentType::UUID)
+///     .insert(3, Segment::Integer(1));
+///
+/// let mut store = MemoryStore::new();
+/// store.set(&tuple!("users", "67e55044-10b1-426f-9247-bb680e5fe0c8", "eu"), b"alice").unwrap();
+///
+/// let report = migration.dry_run(&store, &tuple!("users")).unwrap();
+/// assert_eq!(report.changes.len(), 1);
+/// assert!(report.is_clean());
+///
+/// migration.apply(&mut store, &tuple!("users")).unwrap();
+///
+/// let uuid = [0x67, 0xe5, 0x50, 0x44, 0x10, 0xb1, 0x42, 0x6f, 0x92, 0x47, 0xbb, 0x68, 0x0e, 0x5f, 0xe0, 0xc8];
+/// assert_eq!(store.get(&tuple!("users", "eu", uuid, 1)).unwrap(), Some(b"alice".to_vec()));
+/// # }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Migration {
+    source: Option<TupleSchema>,
+    target: Option<TupleSchema>,
+    steps: Vec<Step>,
+}
+
+/// A key which was (or would be) rewritten
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyChange {
+    pub from: Tuple,
+    pub to: Tuple,
+}
+
+/// What a migration changed, or would change in a dry run
+#[derive(Debug, Default, PartialEq)]
+pub struct MigrationReport {
+    /// The keys which are rewritten
+    pub changes: Vec<KeyChange>,
+    /// The number of keys which the migration leaves as they are
+    pub unchanged: usize,
+    /// The keys which couldn't be migrated, and why
+    pub failures: Vec<(Tuple, TupleError)>,
+    /// The rewritten keys which would replace another key
+    pub collisions: Vec<Tuple>,
+    /// Whether the changes were written to the store
+    pub applied: bool,
+}
+
+/// The report for a migration, along with the values of the keys it rewrites
+struct Plan {
+    report: MigrationReport,
+    values: Vec<Vec<u8>>,
+}
+
+impl Migration {
+    /// Create a migration which leaves keys as they are
+    pub fn new() -> Migration {
+        Migration::default()
+    }
+
+    /// Check keys against `schema` before they are rewritten
+    pub fn source(mut self, schema: TupleSchema) -> Migration {
+        self.source = Some(schema);
+        self
+    }
+
+    /// Check keys against `schema` after they are rewritten
+    pub fn target(mut self, schema: TupleSchema) -> Migration {
+        self.target = Some(schema);
+        self
+    }
+
+    /// Move the element at `from` so that it ends up at `to`, shifting the elements in between
+    pub fn move_element(self, from: usize, to: usize) -> Migration {
+        self.step(Step::Move { from, to })
+    }
+
+    /// Insert a default value at `index`
+    pub fn insert(self, index: usize, value: Segment) -> Migration {
+        self.step(Step::Insert { index, value })
+    }
+
+    /// Remove the element at `index`
+    pub fn remove(self, index: usize) -> Migration {
+        self.step(Step::Remove { index })
+    }
+
+    /// Convert the element at `index` to another type
+    pub fn convert(self, index: usize, to: SegmentType) -> Migration {
+        self.step(Step::Convert { index, to })
+    }
+
+    /// Add an edit, which is applied after the existing ones
+    pub fn step(mut self, step: Step) -> Migration {
+        self.steps.push(step);
+        self
+    }
+
+    /// The edits made to each key, in order
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+
+    /// Rewrite a single key
+    ///
+    /// Fails if the key doesn't match the source schema, an edit refers to a missing element
+    /// (`TupleError::IndexOutOfBounds`), an element can't be converted
+    /// (`TupleError::ConversionFailed`), or the result doesn't match the target schema.
+    pub fn migrate_key(&self, key: &[u8]) -> Result<Tuple, TupleError> {
+        if let Some(ref schema) = self.source {
+            schema.validate(key)?;
+        }
+
+        let mut tuple = Tuple::from_bytes(key);
+        for step in self.steps.iter() {
+            match step {
+                Step::Move { from, to } => {
+                    let segment = tuple.remove(*from)?;
+                    tuple.insert(*to, &segment)?;
+                }
+                Step::Insert { index, value } => tuple.insert(*index, value)?,
+                Step::Remove { index } => {
+                    tuple.remove(*index)?;
+                }
+                Step::Convert { index, to } => {
+                    let segment = {
+                        let elements = tuple.index()?;
+                        match elements.segment(*index) {
+                            Some(segment) => segment?,
+                            None => return Err(TupleError::IndexOutOfBounds { index: *index, len: elements.len() }),
+                        }
+                    };
+                    let from = segment.segment_type();
+                    let converted = convert(segment, *to)
+                        .ok_or(TupleError::ConversionFailed { index: *index, from, to: *to })?;
+                    tuple.replace(*index, &converted)?;
+                }
+            }
+        }
+
+        if let Some(ref schema) = self.target {
+            schema.validate(&tuple)?;
+        }
+
+        Ok(tuple)
+    }
+
+    /// Report how the keys containing `prefix` would be rewritten, without changing the store
+    pub fn dry_run<S: KeyValueStore>(&self, store: &S, prefix: &Tuple) -> Result<MigrationReport, StoreError<S::Error>> {
+        self.plan(store, prefix).map(|plan| plan.report)
+    }
+
+    /// Rewrite the keys containing `prefix`, moving their values to the new keys
+    ///
+    /// Nothing is written unless every key can be migrated without colliding with another key -
+    /// otherwise the report lists the problems and `applied` is false.
+    ///
+    /// The changes are written as a single `KeyValueStore::write_batch`, which is atomic for the
+    /// sled and LMDB stores. Stores which make batched writes one at a time can fail part way
+    /// through, so the writes are ordered to keep every value in the store: a key is only
+    /// overwritten once its own value has been moved, and an old key is only cleared once its value
+    /// is written to the new key. Keys which swap places (`A -> B` and `B -> A`) are the exception,
+    /// as one of them is overwritten while its value is held only in memory, and that value is lost
+    /// if a later write fails. Migrations should be run while nothing else is writing to the prefix.
+    pub fn apply<S: KeyValueStore>(&self, store: &mut S, prefix: &Tuple) -> Result<MigrationReport, StoreError<S::Error>> {
+        let Plan { mut report, values } = self.plan(store, prefix)?;
+        if !report.is_clean() {
+            return Ok(report);
+        }
+
+        let targets = report.changes.iter().map(|change| change.to.as_bytes()).collect::<BTreeSet<_>>();
+        let mut batch = Vec::with_capacity(report.changes.len() * 2);
+        for index in write_order(&report.changes) {
+            let change = &report.changes[index];
+            batch.push(BatchOp::Set(change.to.as_bytes().to_vec(), values[index].clone()));
+            // Keys which another change writes to are replaced rather than cleared
+            if !targets.contains(change.from.as_bytes()) {
+                batch.push(BatchOp::Clear(change.from.as_bytes().to_vec()));
+            }
+        }
+        store.write_batch(&batch).map_err(StoreError::Backend)?;
+
+        report.applied = true;
+        Ok(report)
+    }
+
+    fn plan<S: KeyValueStore>(&self, store: &S, prefix: &Tuple) -> Result<Plan, StoreError<S::Error>> {
+        let (begin, end) = prefix.range();
+        let mut report = MigrationReport::default();
+        let mut values = Vec::new();
+
+        for (key, value) in store.scan_raw(&begin, &end).map_err(StoreError::Backend)? {
+            match self.migrate_key(&key) {
+                Ok(migrated) if migrated.as_bytes() == &key[..] => report.unchanged += 1,
+                Ok(migrated) => {
+                    report.changes.push(KeyChange { from: Tuple::from(key), to: migrated });
+                    values.push(value);
+                }
+                Err(err) => report.failures.push((Tuple::from(key), err)),
+            }
+        }
+
+        let moving = report.changes.iter().map(|change| change.from.as_bytes()).collect::<BTreeSet<_>>();
+        let mut targets = BTreeSet::new();
+        for change in report.changes.iter() {
+            let key = change.to.as_bytes();
+            if !targets.insert(key) || (!moving.contains(key) && store.get_raw(key).map_err(StoreError::Backend)?.is_some()) {
+                report.collisions.push(change.to.clone());
+            }
+        }
+
+        Ok(Plan { report, values })
+    }
+}
+
+/// The order to write changes in, so that each key is moved before another change overwrites it
+///
+/// Changes form chains (`A -> B`, `B -> C`, ...) which are written from the end backwards. A cycle
+/// has no end, so it is broken at the change which it started from.
+fn write_order(changes: &[KeyChange]) -> Vec<usize> {
+    let by_from = changes.iter().enumerate()
+        .map(|(index, change)| (change.from.as_bytes(), index))
+        .collect::<BTreeMap<_, _>>();
+    let mut visited = vec![false; changes.len()];
+    let mut order = Vec::with_capacity(changes.len());
+
+    for start in 0..changes.len() {
+        let mut chain = Vec::new();
+        let mut next = Some(start);
+        while let Some(index) = next {
+            if visited[index] {
+                break;
+            }
+            visited[index] = true;
+            chain.push(index);
+            next = by_from.get(changes[index].to.as_bytes()).cloned();
+        }
+        order.extend(chain.into_iter().rev());
+    }
+
+    order
+}
+
+impl MigrationReport {
+    /// Whether every key can be migrated without collisions
+    pub fn is_clean(&self) -> bool {
+        self.failures.is_empty() && self.collisions.is_empty()
+    }
+}
+
+impl fmt::Display for MigrationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for change in self.changes.iter() {
+            writeln!(f, "{:?} -> {:?}", change.from, change.to)?;
+        }
+        for (key, err) in self.failures.iter() {
+            writeln!(f, "{:?}: {}", key, err)?;
+        }
+        for key in self.collisions.iter() {
+            writeln!(f, "{:?}: collides with another key", key)?;
+        }
+
+        write!(
+            f,
+            "{} changed, {} unchanged, {} failed, {} collisions",
+            self.changes.len(), self.unchanged, self.failures.len(), self.collisions.len()
+        )
+    }
+}
+
+/// Convert a segment to another type, returning `None` if it can't be represented
+///
+/// Strings convert to and from UUIDs (in their hyphenated form), integers, and UTF-8 byte strings,
+/// and integers and floats widen to doubles - integers beyond ±2<sup>53</sup> only when they can be
+/// represented exactly. Converting to a segment's own type leaves it as it is.
+pub fn convert(segment: Segment, to: SegmentType) -> Option<Segment> {
+    if segment.segment_type() == to {
+        return Some(segment);
+    }
+
+    match (segment, to) {
+        (Segment::String(value), SegmentType::UUID) => text::parse_uuid(&value).map(Segment::UUID),
+        (Segment::String(value), SegmentType::Integer) => value.parse().ok().map(Segment::Integer),
+        (Segment::String(value), SegmentType::Bytes) => Some(Segment::Bytes(value.into_bytes())),
+        (Segment::Const(value), to) => convert(Segment::String(String::from(value)), to),
+        (Segment::UUID(value), SegmentType::String) => {
+            let mut output = String::with_capacity(36);
+            text::write_uuid(&mut output, &value).ok()?;
+            Some(Segment::String(output))
+        }
+        (Segment::Integer(value), SegmentType::String) => Some(Segment::String(value.to_string())),
+        (Segment::Integer(value), SegmentType::Double) => {
+            // i64::MAX rounds up to 2^63, which would saturate back to i64::MAX
+            let double = value as f64;
+            if double as i64 == value && double < i64::MAX as f64 {
+                Some(Segment::Double(double))
+            } else {
+                None
+            }
+        }
+        (Segment::Float(value), SegmentType::Double) => Some(Segment::Double(value as f64)),
+        (Segment::Bytes(value), SegmentType::String) => String::from_utf8(value).ok().map(Segment::String),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use store::{MemoryStore, RawEntries};
+
+    const UUID: [u8; 16] = [0x67, 0xe5, 0x50, 0x44, 0x10, 0xb1, 0x42, 0x6f, 0x92, 0x47, 0xbb, 0x68, 0x0e, 0x5f, 0xe0, 0xc8];
+
+    #[test]
+    fn test_steps() {
+        let key = ::tuple!("users", 1, "eu");
+
+        assert_eq!(Migration::new().move_element(2, 1).migrate_key(key.as_bytes()), Ok(::tuple!("users", "eu", 1)));
+        assert_eq!(Migration::new().move_element(0, 2).migrate_key(key.as_bytes()), Ok(::tuple!(1, "eu", "users")));
+        assert_eq!(Migration::new().insert(3, Segment::Integer(0)).migrate_key(key.as_bytes()), Ok(::tuple!("users", 1, "eu", 0)));
+        assert_eq!(Migration::new().remove(0).migrate_key(key.as_bytes()), Ok(::tuple!(1, "eu")));
+        assert_eq!(Migration::new().convert(1, SegmentType::String).migrate_key(key.as_bytes()), Ok(::tuple!("users", "1", "eu")));
+        assert_eq!(Migration::new().migrate_key(key.as_bytes()), Ok(key.clone()));
+    }
+
+    #[test]
+    fn test_step_errors() {
+        let key = ::tuple!("users", 1);
+
+        assert_eq!(Migration::new().remove(2).migrate_key(key.as_bytes()), Err(TupleError::IndexOutOfBounds { index: 2, len: 2 }));
+        assert_eq!(Migration::new().convert(2, SegmentType::String).migrate_key(key.as_bytes()), Err(TupleError::IndexOutOfBounds { index: 2, len: 2 }));
+        assert_eq!(Migration::new().convert(0, SegmentType::UUID).migrate_key(key.as_bytes()), Err(TupleError::ConversionFailed {
+            index: 0,
+            from: SegmentType::String,
+            to: SegmentType::UUID,
+        }));
+    }
+
+    #[test]
+    fn test_schemas() {
+        let migration = Migration::new()
+            .source(TupleSchema::new().element(SegmentType::String).element(SegmentType::String))
+            .convert(1, SegmentType::UUID)
+            .target(TupleSchema::new().element(SegmentType::String).element(SegmentType::UUID));
+
+        assert_eq!(migration.migrate_key(::tuple!("users", "67e55044-10b1-426f-9247-bb680e5fe0c8").as_bytes()), Ok(::tuple!("users", UUID)));
+        assert!(migration.migrate_key(::tuple!("users", 1).as_bytes()).is_err());
+
+        let migration = Migration::new().insert(0, Segment::Integer(1)).target(TupleSchema::new().element(SegmentType::String));
+        assert!(migration.migrate_key(::tuple!("users").as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_convert() {
+        assert_eq!(convert(Segment::UUID(UUID), SegmentType::String), Some(Segment::String(String::from("67e55044-10b1-426f-9247-bb680e5fe0c8"))));
+        assert_eq!(convert(Segment::Const("42"), SegmentType::Integer), Some(Segment::Integer(42)));
+        assert_eq!(convert(Segment::Integer(2), SegmentType::Double), Some(Segment::Double(2.0)));
+        assert_eq!(convert(Segment::Integer(1 << 53), SegmentType::Double), Some(Segment::Double(9007199254740992.0)));
+        assert_eq!(convert(Segment::Integer((1 << 53) + 1), SegmentType::Double), None);
+        assert_eq!(convert(Segment::Integer(-(1 << 53) - 1), SegmentType::Double), None);
+        assert_eq!(convert(Segment::Integer(i64::MIN), SegmentType::Double), Some(Segment::Double(-9223372036854775808.0)));
+        assert_eq!(convert(Segment::Integer(i64::MAX), SegmentType::Double), None);
+        assert_eq!(convert(Segment::Float(1.5), SegmentType::Double), Some(Segment::Double(1.5)));
+        assert_eq!(convert(Segment::Bytes(vec![104, 105]), SegmentType::String), Some(Segment::String(String::from("hi"))));
+        assert_eq!(convert(Segment::String(String::from("hi")), SegmentType::Bytes), Some(Segment::Bytes(vec![104, 105])));
+        assert_eq!(convert(Segment::Bytes(vec![0xFF]), SegmentType::String), None);
+        assert_eq!(convert(Segment::Boolean(true), SegmentType::Integer), None);
+        assert_eq!(convert(Segment::Boolean(true), SegmentType::Boolean), Some(Segment::Boolean(true)));
+    }
+
+    fn store() -> MemoryStore {
+        let mut store = MemoryStore::new();
+        store.set(&::tuple!("users", 1, "eu"), b"alice").unwrap();
+        store.set(&::tuple!("users", 2, "us"), b"bob").unwrap();
+        store.set(&::tuple!("posts", 1), b"hello").unwrap();
+        store
+    }
+
+    #[test]
+    fn test_dry_run() {
+        let store = store();
+        let report = Migration::new().move_element(2, 1).dry_run(&store, &::tuple!("users")).unwrap();
+
+        assert_eq!(report.changes, vec![
+            KeyChange { from: ::tuple!("users", 1, "eu"), to: ::tuple!("users", "eu", 1) },
+            KeyChange { from: ::tuple!("users", 2, "us"), to: ::tuple!("users", "us", 2) },
+        ]);
+        assert!(report.is_clean());
+        assert!(!report.applied);
+        assert_eq!(report.to_string(), "Tuple(\"users\", 1, \"eu\") -> Tuple(\"users\", \"eu\", 1)\n\
+            Tuple(\"users\", 2, \"us\") -> Tuple(\"users\", \"us\", 2)\n\
+            2 changed, 0 unchanged, 0 failed, 0 collisions");
+        assert_eq!(store.get(&::tuple!("users", 1, "eu")), Ok(Some(b"alice".to_vec())));
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut store = store();
+        let report = Migration::new().move_element(2, 1).apply(&mut store, &::tuple!("users")).unwrap();
+
+        assert!(report.applied);
+        assert_eq!(store.get(&::tuple!("users", "eu", 1)), Ok(Some(b"alice".to_vec())));
+        assert_eq!(store.get(&::tuple!("users", 1, "eu")), Ok(None));
+        assert_eq!(store.get(&::tuple!("posts", 1)), Ok(Some(b"hello".to_vec())));
+        assert_eq!(store.len(), 3);
+    }
+
+    #[test]
+    fn test_swapped_keys() {
+        let mut store = MemoryStore::new();
+        store.set(&::tuple!("pairs", 1, 2), b"a").unwrap();
+        store.set(&::tuple!("pairs", 2, 1), b"b").unwrap();
+
+        let report = Migration::new().move_element(2, 1).apply(&mut store, &::tuple!("pairs")).unwrap();
+
+        assert!(report.applied);
+        assert_eq!(store.get(&::tuple!("pairs", 2, 1)), Ok(Some(b"a".to_vec())));
+        assert_eq!(store.get(&::tuple!("pairs", 1, 2)), Ok(Some(b"b".to_vec())));
+    }
+
+    #[test]
+    fn test_chained_keys() {
+        let mut store = MemoryStore::new();
+        store.set(&::tuple!("chain", 1), b"one").unwrap();
+        store.set(&::tuple!("chain", 1, 1), b"two").unwrap();
+
+        // ("chain", 1) -> ("chain", 1, 1) -> ("chain", 1, 1, 1)
+        let report = Migration::new().insert(1, Segment::Integer(1)).apply(&mut store, &::tuple!("chain")).unwrap();
+
+        assert!(report.applied);
+        assert_eq!(store.get(&::tuple!("chain", 1)), Ok(None));
+        assert_eq!(store.get(&::tuple!("chain", 1, 1)), Ok(Some(b"one".to_vec())));
+        assert_eq!(store.get(&::tuple!("chain", 1, 1, 1)), Ok(Some(b"two".to_vec())));
+        assert_eq!(store.len(), 2);
+    }
+
+    #[test]
+    fn test_write_order() {
+        let change = |from: i64, to: i64| KeyChange { from: ::tuple!(from), to: ::tuple!(to) };
+
+        assert_eq!(write_order(&[change(1, 2), change(2, 3), change(3, 4)]), vec![2, 1, 0]);
+        assert_eq!(write_order(&[change(3, 4), change(1, 2), change(2, 3)]), vec![0, 2, 1]);
+        assert_eq!(write_order(&[change(1, 2), change(2, 1), change(5, 6)]), vec![1, 0, 2]);
+    }
+
+    /// A store which fails every write after the first `writes`
+    struct FailingStore {
+        store: MemoryStore,
+        writes: usize,
+    }
+
+    impl KeyValueStore for FailingStore {
+        type Error = ();
+
+        fn get_raw(&self, key: &[u8]) -> Result<Option<Vec<u8>>, ()> {
+            Ok(self.store.get_raw(key).unwrap())
+        }
+
+        fn set_raw(&mut self, key: &[u8], value: &[u8]) -> Result<(), ()> {
+            self.writes = self.writes.checked_sub(1).ok_or(())?;
+            self.store.set_raw(key, value).map_err(|_| ())
+        }
+
+        fn clear_raw(&mut self, key: &[u8]) -> Result<(), ()> {
+            self.writes = self.writes.checked_sub(1).ok_or(())?;
+            self.store.clear_raw(key).map_err(|_| ())
+        }
+
+        fn scan_raw(&self, begin: &[u8], end: &[u8]) -> Result<RawEntries, ()> {
+            Ok(self.store.scan_raw(begin, end).unwrap())
+        }
+    }
+
+    #[test]
+    fn test_failed_apply_keeps_values() {
+        // The migration makes three writes, so fail at each of them
+        for writes in 0..3 {
+            let mut store = FailingStore { store: MemoryStore::new(), writes: usize::MAX };
+            store.set(&::tuple!("chain", 1), b"one").unwrap();
+            store.set(&::tuple!("chain", 1, 1), b"two").unwrap();
+            store.writes = writes;
+
+            let migration = Migration::new().insert(1, Segment::Integer(1));
+            assert_eq!(migration.apply(&mut store, &::tuple!("chain")), Err(StoreError::Backend(())));
+
+            let values = store.scan_prefix(&::tuple!("chain")).unwrap().into_iter().map(|(_, value)| value).collect::<Vec<_>>();
+            assert!(values.contains(&b"one".to_vec()), "lost a value after {} writes", writes);
+            assert!(values.contains(&b"two".to_vec()), "lost a value after {} writes", writes);
+        }
+    }
+
+    #[test]
+    fn test_problems_block_apply() {
+        let mut store = store();
+        store.set(&::tuple!("users", 1), b"unchanged").unwrap();
+
+        let report = Migration::new().remove(2).apply(&mut store, &::tuple!("users")).unwrap();
+
+        assert!(!report.applied);
+        assert_eq!(report.failures, vec![(::tuple!("users", 1), TupleError::IndexOutOfBounds { index: 2, len: 2 })]);
+        assert_eq!(report.collisions, vec![::tuple!("users", 1)]);
+        assert_eq!(store.get(&::tuple!("users", 1, "eu")), Ok(Some(b"alice".to_vec())));
+        assert_eq!(store.get(&::tuple!("users", 1)), Ok(Some(b"unchanged".to_vec())));
+    }
+
+    #[test]
+    fn test_duplicate_targets() {
+        let mut store = store();
+        store.set(&::tuple!("users", 1, "us"), b"carol").unwrap();
+
+        let report = Migration::new().remove(2).apply(&mut store, &::tuple!("users")).unwrap();
+
+        assert!(!report.applied);
+        assert!(report.failures.is_empty());
+        assert_eq!(report.collisions, vec![::tuple!("users", 1)]);
+        assert_eq!(store.len(), 4);
+    }
+}
@@ -0,0 +1,277 @@
+//! Converting Rust values to and from tuples
+//!
+//! `TuplePack` appends a value to a tuple as one or more elements, and `FromTuple` reads it back
+//! from the decoded elements. Both are implemented for the element types and for Rust tuples of
+//! up to six values, so composite keys such as `(String, i64)` can be used directly.
+
+use alloc::string::String;
+use alloc::vec::{IntoIter, Vec};
+use errors::TupleError;
+use segment::{Segment, SegmentType};
+#[cfg(feature = "uuid")]
+use uuid::Uuid;
+use Tuple;
+
+/// A value which can be appended to a tuple
+///
+/// # Examples
+/// ```
+/// #[macro_use] extern crate binary_tuples;
+///
+/// use binary_tuples::{FromTuple, TuplePack};
+///
+/// # fn main() {
+/// let key = ("users", 1234i64).pack();
+///
+/// assert_eq!(key.as_bytes(), tuple!("users", 1234).as_bytes());
+/// assert_eq!(<(String, i64)>::from_tuple(key.as_bytes()), Ok((String::from("users"), 1234)));
+/// # }
+/// ```
+pub trait TuplePack {
+    /// Append the elements of this value to a tuple
+    fn pack_into(&self, tuple: &mut Tuple);
+
+    /// Encode this value as a new tuple
+    fn pack(&self) -> Tuple {
+        let mut tuple = Tuple::new();
+        self.pack_into(&mut tuple);
+        tuple
+    }
+}
+
+/// A value which can be read back from the elements of a tuple
+pub trait FromTuple: Sized {
+    /// Read this value from the front of the remaining elements
+    ///
+    /// Fails with `TupleError::MissingElement` if the elements run out, or
+    /// `TupleError::TypeMismatch` if an element has the wrong type.
+    fn unpack(segments: &mut IntoIter<Segment>) -> Result<Self, TupleError>;
+
+    /// Read this value from decoded elements, failing if any are left over
+    fn from_segments(segments: Vec<Segment>) -> Result<Self, TupleError> {
+        let mut segments = segments.into_iter();
+        let value = Self::unpack(&mut segments)?;
+
+        match segments.len() {
+            0 => Ok(value),
+            count => Err(TupleError::TrailingElements { count }),
+        }
+    }
+
+    /// Decode this value from an encoded tuple, failing if any elements are left over
+    fn from_tuple(input: &[u8]) -> Result<Self, TupleError> {
+        Self::from_segments(Segment::decode(input)?)
+    }
+}
+
+fn next_segment(segments: &mut IntoIter<Segment>) -> Result<Segment, TupleError> {
+    segments.next().ok_or(TupleError::MissingElement)
+}
+
+fn mismatch(expected: SegmentType, found: &Segment) -> TupleError {
+    TupleError::TypeMismatch { expected, found: found.segment_type() }
+}
+
+impl<T: TuplePack + ?Sized> TuplePack for &T {
+    fn pack_into(&self, tuple: &mut Tuple) {
+        (**self).pack_into(tuple)
+    }
+}
+
+impl TuplePack for () {
+    fn pack_into(&self, _tuple: &mut Tuple) {}
+}
+
+impl FromTuple for () {
+    fn unpack(_segments: &mut IntoIter<Segment>) -> Result<(), TupleError> {
+        Ok(())
+    }
+}
+
+impl TuplePack for Segment {
+    fn pack_into(&self, tuple: &mut Tuple) {
+        tuple.add_segment(self)
+    }
+}
+
+impl FromTuple for Segment {
+    fn unpack(segments: &mut IntoIter<Segment>) -> Result<Segment, TupleError> {
+        next_segment(segments)
+    }
+}
+
+impl TuplePack for Tuple {
+    fn pack_into(&self, tuple: &mut Tuple) {
+        tuple.add_builder(self)
+    }
+}
+
+impl TuplePack for str {
+    fn pack_into(&self, tuple: &mut Tuple) {
+        tuple.add_segment(&Segment::String(String::from(self)))
+    }
+}
+
+impl TuplePack for String {
+    fn pack_into(&self, tuple: &mut Tuple) {
+        self.as_str().pack_into(tuple)
+    }
+}
+
+impl FromTuple for String {
+    fn unpack(segments: &mut IntoIter<Segment>) -> Result<String, TupleError> {
+        match next_segment(segments)? {
+            Segment::String(value) => Ok(value),
+            Segment::Const(value) => Ok(String::from(value)),
+            other => Err(mismatch(SegmentType::String, &other)),
+        }
+    }
+}
+
+impl TuplePack for [u8] {
+    fn pack_into(&self, tuple: &mut Tuple) {
+        tuple.add_segment(&Segment::Bytes(Vec::from(self)))
+    }
+}
+
+impl TuplePack for Vec<u8> {
+    fn pack_into(&self, tuple: &mut Tuple) {
+        self.as_slice().pack_into(tuple)
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl TuplePack for Uuid {
+    fn pack_into(&self, tuple: &mut Tuple) {
+        tuple.add_segment(&Segment::UUID(*self.as_bytes()))
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl FromTuple for Uuid {
+    fn unpack(segments: &mut IntoIter<Segment>) -> Result<Uuid, TupleError> {
+        <[u8; 16]>::unpack(segments).map(Uuid::from_bytes)
+    }
+}
+
+/// Pack and unpack `Copy` values which map onto a single kind of segment
+macro_rules! segment_impls {
+    ($($ty:ty => $variant:ident),* $(,)*) => {
+        $(
+            impl TuplePack for $ty {
+                fn pack_into(&self, tuple: &mut Tuple) {
+                    tuple.add_segment(&Segment::$variant(*self))
+                }
+            }
+
+            impl FromTuple for $ty {
+                fn unpack(segments: &mut IntoIter<Segment>) -> Result<$ty, TupleError> {
+                    match next_segment(segments)? {
+                        Segment::$variant(value) => Ok(value),
+                        other => Err(mismatch(SegmentType::$variant, &other)),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+segment_impls! {
+    i64 => Integer,
+    f32 => Float,
+    f64 => Double,
+    bool => Boolean,
+    [u8; 16] => UUID,
+}
+
+impl TuplePack for Vec<Segment> {
+    fn pack_into(&self, tuple: &mut Tuple) {
+        tuple.add_segment(&Segment::Nested(self.clone()))
+    }
+}
+
+impl FromTuple for Vec<Segment> {
+    fn unpack(segments: &mut IntoIter<Segment>) -> Result<Vec<Segment>, TupleError> {
+        match next_segment(segments)? {
+            Segment::Nested(value) => Ok(value),
+            other => Err(mismatch(SegmentType::Nested, &other)),
+        }
+    }
+}
+
+impl FromTuple for Vec<u8> {
+    fn unpack(segments: &mut IntoIter<Segment>) -> Result<Vec<u8>, TupleError> {
+        match next_segment(segments)? {
+            Segment::Bytes(value) => Ok(value),
+            other => Err(mismatch(SegmentType::Bytes, &other)),
+        }
+    }
+}
+
+/// Pack and unpack Rust tuples as consecutive elements
+macro_rules! tuple_impls {
+    ($($name:ident)+) => {
+        impl<$($name: TuplePack),+> TuplePack for ($($name,)+) {
+            #[allow(non_snake_case)]
+            fn pack_into(&self, tuple: &mut Tuple) {
+                let ($(ref $name,)+) = *self;
+                $($name.pack_into(tuple);)+
+            }
+        }
+
+        impl<$($name: FromTuple),+> FromTuple for ($($name,)+) {
+            fn unpack(segments: &mut IntoIter<Segment>) -> Result<($($name,)+), TupleError> {
+                Ok(($($name::unpack(segments)?,)+))
+            }
+        }
+    };
+}
+
+tuple_impls! { A }
+tuple_impls! { A B }
+tuple_impls! { A B C }
+tuple_impls! { A B C D }
+tuple_impls! { A B C D E }
+tuple_impls! { A B C D E F }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pack() {
+        let key = ("users", String::from("alice"), 1i64, vec![0u8, 1], true, Segment::Double(1.5)).pack();
+
+        assert_eq!(key.as_segments().unwrap(), vec![
+            Segment::String(String::from("users")),
+            Segment::String(String::from("alice")),
+            Segment::Integer(1),
+            Segment::Bytes(vec![0, 1]),
+            Segment::Boolean(true),
+            Segment::Double(1.5),
+        ]);
+        assert_eq!((::tuple!("users"), 1i64).pack().as_bytes(), ::tuple!("users", 1).as_bytes());
+        assert!(().pack().as_bytes().is_empty());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let value = ((String::from("users"), 1i64), 2.5f32, [7u8; 16], vec![Segment::Integer(3)]);
+        let unpacked = FromTuple::from_tuple(value.pack().as_bytes());
+
+        assert_eq!(unpacked, Ok(value));
+    }
+
+    #[test]
+    fn test_unpack_errors() {
+        let key = ::tuple!("users", 1);
+
+        assert_eq!(<(String, String)>::from_tuple(key.as_bytes()), Err(TupleError::TypeMismatch {
+            expected: SegmentType::String,
+            found: SegmentType::Integer,
+        }));
+        assert_eq!(<(String, i64, i64)>::from_tuple(key.as_bytes()), Err(TupleError::MissingElement));
+        assert_eq!(<(String,)>::from_tuple(key.as_bytes()), Err(TupleError::TrailingElements { count: 1 }));
+        assert_eq!(i64::from_tuple(&[0x99]), Err(TupleError::DecodeError { position: 0, type_code: 0x99 }));
+    }
+}
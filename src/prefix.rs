@@ -0,0 +1,141 @@
+//! Prefix relationships between encoded tuples
+//!
+//! Tuples are compared element by element rather than byte by byte - a byte string containing a
+//! null starts with the same bytes as the byte string which stops at that null, but neither tuple
+//! is a prefix of the other.
+
+use decode::{descending_element_end, element_end, DEFAULT_MAX_DEPTH};
+use descending::is_inverted;
+use Tuple;
+
+impl Tuple {
+    /// Whether the elements of `prefix` are the first elements of this tuple
+    ///
+    /// # Examples
+    /// ```
+    /// #[macro_use] extern crate binary_tuples;
+    ///
+    /// # fn main() {
+    /// let key = tuple!(vec![1, 0, 2]);
+    ///
+    /// assert!(key.starts_with(&tuple!(vec![1, 0, 2])));
+    /// assert!(key.as_bytes().starts_with(tuple!(vec![1]).as_bytes()));
+    /// assert!(!key.starts_with(&tuple!(vec![1])));
+    /// # }
+    /// ```
+    pub fn starts_with(&self, prefix: &Tuple) -> bool {
+        common_prefix_len(&self.buffer, &prefix.buffer) == prefix.buffer.len()
+    }
+
+    /// Remove `prefix` from the start of this tuple, returning the remaining elements or `None` if
+    /// this tuple doesn't start with `prefix`
+    pub fn strip_prefix(&self, prefix: &Tuple) -> Option<Tuple> {
+        if self.starts_with(prefix) {
+            Some(Tuple::from_bytes(&self.buffer[prefix.buffer.len()..]))
+        } else {
+            None
+        }
+    }
+
+    /// The longest run of elements that both tuples start with
+    ///
+    /// # Examples
+    /// ```
+    /// #[macro_use] extern crate binary_tuples;
+    ///
+    /// use binary_tuples::Tuple;
+    ///
+    /// # fn main() {
+    /// let prefix = Tuple::common_prefix(&tuple!("users", 1, "posts"), &tuple!("users", 1, "likes"));
+    ///
+    /// assert_eq!(prefix.as_bytes(), tuple!("users", 1).as_bytes());
+    /// # }
+    /// ```
+    pub fn common_prefix(a: &Tuple, b: &Tuple) -> Tuple {
+        Tuple::from_bytes(&a.buffer[..common_prefix_len(&a.buffer, &b.buffer)])
+    }
+}
+
+/// The length in bytes of the elements which both inputs start with
+///
+/// Comparison stops at the first invalid element in either input. Descending elements are found by
+/// their inverted type codes.
+pub(crate) fn common_prefix_len(a: &[u8], b: &[u8]) -> usize {
+    let mut index = 0;
+
+    while index < a.len() && index < b.len() {
+        match (any_element_end(a, index), any_element_end(b, index)) {
+            (Some(end), Some(other)) if end == other && a[index..end] == b[index..end] => index = end,
+            _ => break,
+        }
+    }
+
+    index
+}
+
+fn any_element_end(input: &[u8], index: usize) -> Option<usize> {
+    if is_inverted(input[index]) {
+        descending_element_end(input, index, DEFAULT_MAX_DEPTH)
+    } else {
+        element_end(input, index, DEFAULT_MAX_DEPTH)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+    use {Descending, TupleRef};
+
+    #[test]
+    fn test_starts_with() {
+        let key = ::tuple!("users", 1, "posts");
+
+        assert!(key.starts_with(&Tuple::new()));
+        assert!(key.starts_with(&::tuple!("users", 1)));
+        assert!(key.starts_with(&key));
+        assert!(!key.starts_with(&::tuple!("user")));
+        assert!(!key.starts_with(&::tuple!("users", 1, "posts", 2)));
+        assert!(!::tuple!("users\0x").starts_with(&::tuple!("users")));
+        assert!(!key.starts_with(&Tuple::from_bytes(&[0x99])));
+    }
+
+    #[test]
+    fn test_strip_prefix() {
+        let key = ::tuple!("users", 1, "posts");
+
+        assert_eq!(key.strip_prefix(&::tuple!("users", 1)).unwrap().as_bytes(), ::tuple!("posts").as_bytes());
+        assert!(key.strip_prefix(&key).unwrap().as_bytes().is_empty());
+        assert!(key.strip_prefix(&::tuple!(vec![0])).is_none());
+    }
+
+    #[test]
+    fn test_descending() {
+        let key = ::tuple!("posts", Descending("ab"), Descending(5));
+
+        assert!(key.starts_with(&key));
+        assert!(key.starts_with(&::tuple!("posts", Descending("ab"))));
+        assert!(!key.starts_with(&::tuple!("posts", Descending("a"))));
+        assert!(!key.starts_with(&::tuple!("posts", "ab")));
+        assert!(key.strip_prefix(&key).unwrap().as_bytes().is_empty());
+        assert_eq!(key.strip_prefix(&::tuple!("posts", Descending("ab"))).unwrap().as_bytes(), ::tuple!(Descending(5)).as_bytes());
+        assert_eq!(Tuple::common_prefix(&key, &key).as_bytes(), key.as_bytes());
+        assert_eq!(Tuple::common_prefix(&key, &::tuple!("posts", Descending("ab"), Descending(6))).as_bytes(), ::tuple!("posts", Descending("ab")).as_bytes());
+
+        let key = key.as_tuple_ref();
+        assert!(key.starts_with(key));
+        assert_eq!(key.strip_prefix(key).map(|rest| rest.as_bytes().len()), Some(0));
+        assert_eq!(TupleRef::common_prefix(key, key).as_bytes(), key.as_bytes());
+    }
+
+    #[test]
+    fn test_common_prefix() {
+        let a = ::tuple!("users", vec![1, 0, 2], Vec::<::Segment>::new());
+        let b = ::tuple!("users", vec![1, 0, 2], 3);
+        let c = ::tuple!("users", vec![1]);
+
+        assert_eq!(Tuple::common_prefix(&a, &b).as_bytes(), ::tuple!("users", vec![1, 0, 2]).as_bytes());
+        assert_eq!(Tuple::common_prefix(&a, &c).as_bytes(), ::tuple!("users").as_bytes());
+        assert!(Tuple::common_prefix(&a, &Tuple::new()).as_bytes().is_empty());
+    }
+}
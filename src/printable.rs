@@ -0,0 +1,310 @@
+//! Escaped "printable" byte strings in the style of the FoundationDB tooling
+//!
+//! Printable ascii characters are written as-is, backslashes are doubled and every other byte is
+//! written as a `\xNN` escape sequence. This is the representation used for keys in FoundationDB
+//! logs, traces and `fdbcli`.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::Write;
+use constants::*;
+use decode::DEFAULT_MAX_DEPTH;
+use errors::TupleError;
+
+/// Format a byte string with all non printable bytes escaped
+///
+/// # Examples
+/// ```
+/// use binary_tuples::printable::printable;
+///
+/// assert_eq!(printable(&[2, 117, 115, 101, 114, 115, 0, 21, 1]), "\\x02users\\x00\\x15\\x01");
+/// ```
+pub fn printable(input: &[u8]) -> String {
+    let mut output = String::with_capacity(input.len());
+
+    for byte in input.iter() {
+        match *byte {
+            b'\\' => output.push_str("\\\\"),
+            32..=126 => output.push(*byte as char),
+            value => write!(output, "\\x{:02x}", value).unwrap(),
+        }
+    }
+
+    output
+}
+
+/// Parse an escaped byte string produced by `printable`
+///
+/// # Examples
+/// ```
+/// use binary_tuples::printable::from_printable;
+///
+/// assert_eq!(from_printable("\\x02users\\x00").unwrap(), vec![2, 117, 115, 101, 114, 115, 0]);
+/// ```
+pub fn from_printable(input: &str) -> Result<Vec<u8>, TupleError> {
+    let input = input.as_bytes();
+    let mut output = Vec::with_capacity(input.len());
+
+    let mut index = 0;
+    while index < input.len() {
+        match input[index] {
+            b'\\' => {
+                match input.get(index + 1) {
+                    Some(b'\\') => {
+                        output.push(b'\\');
+                        index += 2;
+                    }
+                    Some(b'x') if index + 4 <= input.len() => {
+                        let high = hex_digit(input[index + 2]);
+                        let low = hex_digit(input[index + 3]);
+
+                        match (high, low) {
+                            (Some(high), Some(low)) => output.push(high << 4 | low),
+                            _ => return Err(TupleError::ParseError { position: index })
+                        }
+                        index += 4;
+                    }
+                    _ => return Err(TupleError::ParseError { position: index })
+                }
+            }
+            value => {
+                output.push(value);
+                index += 1;
+            }
+        }
+    }
+
+    Ok(output)
+}
+
+fn hex_digit(value: u8) -> Option<u8> {
+    match value {
+        b'0'..=b'9' => Some(value - b'0'),
+        b'a'..=b'f' => Some(value - b'a' + 10),
+        b'A'..=b'F' => Some(value - b'A' + 10),
+        _ => None
+    }
+}
+
+/// Produce a hex dump of an encoded tuple with each byte labelled by its role
+///
+/// Every line contains the offset of the byte, its value, the printable character, the position of
+/// the element it belongs to (nested elements are separated by `.`) and a description of the byte.
+/// Bytes following an invalid or truncated element are labelled as `invalid`.
+///
+/// # Examples
+/// ```
+/// use binary_tuples::printable::hex_dump;
+///
+/// let dump = hex_dump(&[2, 104, 105, 0, 21, 1]);
+///
+/// assert_eq!(dump, "\
+/// 0000  02  .  0    STRING_CODE
+/// 0001  68  h  0    data
+/// 0002  69  i  0    data
+/// 0003  00  .  0    terminator
+/// 0004  15  .  1    INT_POS_CODE (1 byte)
+/// 0005  01  .  1    data
+/// ");
+/// ```
+pub fn hex_dump(input: &[u8]) -> String {
+    let mut labels = vec![(String::new(), "invalid"); input.len()];
+    annotate(input, 0, "", 0, &mut labels);
+
+    let width = labels.iter().map(|label| label.0.len()).max().unwrap_or(0).max(4);
+
+    let mut output = String::new();
+    for (index, (value, (path, description))) in input.iter().zip(labels.iter()).enumerate() {
+        let character = match *value {
+            32..=126 => *value as char,
+            _ => '.',
+        };
+
+        writeln!(output, "{:04x}  {:02x}  {}  {:width$} {}", index, value, character, path, description, width = width).unwrap();
+    }
+
+    output
+}
+
+/// Label the bytes of the elements starting at `index`, returning the position following the last
+/// element or `None` if an invalid element was found.
+///
+/// Tuples nested deeper than `DEFAULT_MAX_DEPTH` are left labelled as invalid, as they would fail to
+/// decode.
+fn annotate(input: &[u8], mut index: usize, prefix: &str, depth: usize, labels: &mut [(String, &'static str)]) -> Option<usize> {
+    let nested = depth > 0;
+    let mut element = 0;
+
+    while index < input.len() {
+        let code = input[index];
+        if code == NULL && nested {
+            labels[index] = (String::from(prefix), "end of nested tuple");
+            return Some(index + 1);
+        }
+
+        let path = if prefix.is_empty() {
+            element.to_string()
+        } else {
+            format!("{}.{}", prefix, element)
+        };
+
+        let (description, length) = match code {
+            BYTES_CODE => ("BYTES_CODE", None),
+            STRING_CODE => ("STRING_CODE", None),
+            NESTED_CODE => ("NESTED_CODE", None),
+            INT_ZERO_CODE => ("INT_ZERO_CODE", Some(0)),
+            INT_NEG_MIN_CODE..=INT_NEG_MAX_CODE => (INT_NEG_DESCRIPTIONS[(INT_ZERO_CODE - code - 1) as usize], Some((INT_ZERO_CODE - code) as usize)),
+            INT_POS_MIN_CODE..=INT_POS_MAX_CODE => (INT_POS_DESCRIPTIONS[(code - INT_ZERO_CODE - 1) as usize], Some((code - INT_ZERO_CODE) as usize)),
+            FLOAT_CODE => ("FLOAT_CODE", Some(4)),
+            DOUBLE_CODE => ("DOUBLE_CODE", Some(8)),
+            FALSE_CODE => ("FALSE_CODE", Some(0)),
+            TRUE_CODE => ("TRUE_CODE", Some(0)),
+            UUID_CODE => ("UUID_CODE", Some(16)),
+            _ => {
+                labels[index] = (path, "unknown type code");
+                return None;
+            }
+        };
+        labels[index] = (path.clone(), description);
+        index += 1;
+
+        index = match (code, length) {
+            (NESTED_CODE, _) if depth < DEFAULT_MAX_DEPTH => annotate(input, index, &path, depth + 1, labels)?,
+            (NESTED_CODE, _) => return None,
+            (_, Some(length)) => {
+                for label in labels.iter_mut().skip(index).take(length) {
+                    *label = (path.clone(), "data");
+                }
+
+                if index + length > input.len() {
+                    return None;
+                }
+                index + length
+            }
+            (_, None) => annotate_byte_string(input, index, &path, labels)?,
+        };
+
+        element += 1;
+    }
+
+    if nested {
+        None
+    } else {
+        Some(index)
+    }
+}
+
+fn annotate_byte_string(input: &[u8], mut index: usize, path: &str, labels: &mut [(String, &'static str)]) -> Option<usize> {
+    while index < input.len() {
+        if input[index] != NULL {
+            labels[index] = (String::from(path), "data");
+            index += 1;
+        } else if input.get(index + 1) == Some(&NULL_ESCAPE) {
+            labels[index] = (String::from(path), "escaped null");
+            labels[index + 1] = (String::from(path), "escape");
+            index += 2;
+        } else {
+            labels[index] = (String::from(path), "terminator");
+            return Some(index + 1);
+        }
+    }
+
+    None
+}
+
+const INT_NEG_DESCRIPTIONS: [&str; 8] = [
+    "INT_NEG_CODE (1 byte)",
+    "INT_NEG_CODE (2 bytes)",
+    "INT_NEG_CODE (3 bytes)",
+    "INT_NEG_CODE (4 bytes)",
+    "INT_NEG_CODE (5 bytes)",
+    "INT_NEG_CODE (6 bytes)",
+    "INT_NEG_CODE (7 bytes)",
+    "INT_NEG_CODE (8 bytes)",
+];
+
+const INT_POS_DESCRIPTIONS: [&str; 8] = [
+    "INT_POS_CODE (1 byte)",
+    "INT_POS_CODE (2 bytes)",
+    "INT_POS_CODE (3 bytes)",
+    "INT_POS_CODE (4 bytes)",
+    "INT_POS_CODE (5 bytes)",
+    "INT_POS_CODE (6 bytes)",
+    "INT_POS_CODE (7 bytes)",
+    "INT_POS_CODE (8 bytes)",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_printable() {
+        assert_eq!(printable(b"a\\b\x00\xff~"), "a\\\\b\\x00\\xff~");
+    }
+
+    #[test]
+    fn test_from_printable() {
+        assert_eq!(from_printable("a\\\\b\\x00\\xFF~").unwrap(), b"a\\b\x00\xff~".to_vec());
+    }
+
+    #[test]
+    fn test_printable_round_trip() {
+        let input = (0..=255).collect::<Vec<u8>>();
+
+        assert_eq!(from_printable(&printable(&input)).unwrap(), input);
+    }
+
+    #[test]
+    fn test_from_printable_invalid() {
+        assert_eq!(from_printable("ab\\x0"), Err(TupleError::ParseError { position: 2 }));
+        assert_eq!(from_printable("\\xzz"), Err(TupleError::ParseError { position: 0 }));
+        assert_eq!(from_printable("\\n"), Err(TupleError::ParseError { position: 0 }));
+    }
+
+    #[test]
+    fn test_hex_dump_nested() {
+        let dump = hex_dump(&[NESTED_CODE, BYTES_CODE, 0, 255, 0, NULL, INT_ZERO_CODE - 1, 254]);
+
+        assert_eq!(dump, "\
+0000  05  .  0    NESTED_CODE
+0001  01  .  0.0  BYTES_CODE
+0002  00  .  0.0  escaped null
+0003  ff  .  0.0  escape
+0004  00  .  0.0  terminator
+0005  00  .  0    end of nested tuple
+0006  13  .  1    INT_NEG_CODE (1 byte)
+0007  fe  .  1    data
+");
+    }
+
+    #[test]
+    fn test_hex_dump_invalid() {
+        let dump = hex_dump(&[TRUE_CODE, 0x99, FALSE_CODE]);
+
+        assert_eq!(dump, "\
+0000  27  '  0    TRUE_CODE
+0001  99  .  1    unknown type code
+0002  26  &       invalid
+");
+    }
+
+    #[test]
+    fn test_hex_dump_truncated() {
+        let dump = hex_dump(&[DOUBLE_CODE, 1, 2]);
+
+        assert_eq!(dump, "\
+0000  21  !  0    DOUBLE_CODE
+0001  01  .  0    data
+0002  02  .  0    data
+");
+    }
+
+    #[test]
+    fn test_hex_dump_too_deep() {
+        let dump = hex_dump(&vec![NESTED_CODE; 100_000]);
+
+        assert!(dump.lines().nth(DEFAULT_MAX_DEPTH).unwrap().ends_with("NESTED_CODE"));
+        assert!(dump.lines().nth(DEFAULT_MAX_DEPTH + 1).unwrap().ends_with("invalid"));
+    }
+}
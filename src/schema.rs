@@ -0,0 +1,387 @@
+//! Describing and checking the shape of tuples
+//!
+//! A `TupleSchema` lists the type expected at each position of a tuple, so malformed keys are
+//! rejected (with the position of the offending element) as they are decoded, rather than deep in
+//! the code which uses them. Schemas also mark the positions of descending elements, which can't
+//! be decoded without one.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use decode::DecodeOptions;
+use descending;
+use errors::TupleError;
+use segment::{Segment, SegmentType};
+
+/// The expected shape of a tuple
+///
+/// Tuples start with the required elements, followed by up to as many optional elements, and
+/// then any number of elements matching the rest schema (if there is one).
+///
+/// # Examples
+/// ```
+/// #[macro_use] extern crate binary_tuples;
+///
+/// use binary_tuples::TupleSchema;
+/// use binary_tuples::segment::{Segment, SegmentType};
+///
+/// # fn main() {
+/// let schema = TupleSchema::new()
+///     .element(SegmentType::String)
+///     .element(SegmentType::Integer)
+///     .optional(TupleSchema::new().rest(SegmentType::String));
+///
+/// assert!(schema.validate(tuple!("users", 1).as_bytes()).is_ok());
+/// assert!(schema.validate(tuple!("users", 1, vec![Segment::Const("admin")])).is_ok());
+///
+/// let err = schema.validate(tuple!("users", "1").as_bytes()).unwrap_err();
+/// assert_eq!(err.to_string(), "element 1 expected integer, found string");
+/// # }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TupleSchema {
+    required: Vec<ElementSchema>,
+    optional: Vec<ElementSchema>,
+    rest: Option<Box<ElementSchema>>,
+}
+
+/// The expected shape of a single element
+#[derive(Clone, Debug, PartialEq)]
+pub enum ElementSchema {
+    /// Any element
+    Any,
+    /// An element of the given type
+    Type(SegmentType),
+    /// A nested tuple matching a schema of its own
+    Nested(TupleSchema),
+    /// An element encoded in descending order (see `descending::Descending`), which is only
+    /// supported at the top level of a tuple
+    Descending(Box<ElementSchema>),
+}
+
+/// Check that an element doesn't nest descending elements inside a tuple, where they can't be
+/// decoded
+fn top_level(element: ElementSchema) -> Result<ElementSchema, TupleError> {
+    fn nests_descending(element: &ElementSchema) -> bool {
+        match element {
+            ElementSchema::Nested(schema) => schema.has_descending(),
+            ElementSchema::Descending(inner) => nests_descending(inner),
+            _ => false,
+        }
+    }
+
+    if nests_descending(&element) {
+        return Err(TupleError::NestedDescending);
+    }
+    Ok(element)
+}
+
+impl From<SegmentType> for ElementSchema {
+    fn from(segment_type: SegmentType) -> ElementSchema {
+        ElementSchema::Type(segment_type)
+    }
+}
+
+impl From<TupleSchema> for ElementSchema {
+    fn from(schema: TupleSchema) -> ElementSchema {
+        ElementSchema::Nested(schema)
+    }
+}
+
+impl TupleSchema {
+    /// A schema which only matches the empty tuple
+    pub fn new() -> TupleSchema {
+        TupleSchema::default()
+    }
+
+    /// Add a required element
+    ///
+    /// # Panics
+    /// If optional elements have already been added, as required elements must come first, or if
+    /// the element is a nested tuple with descending elements (see `try_element`).
+    pub fn element<E: Into<ElementSchema>>(self, element: E) -> TupleSchema {
+        self.try_element(element).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Add a required element, failing with `TupleError::NestedDescending` if it is a nested tuple
+    /// with descending elements, which are only supported at the top level of a tuple
+    ///
+    /// # Panics
+    /// If optional elements have already been added, as required elements must come first.
+    pub fn try_element<E: Into<ElementSchema>>(mut self, element: E) -> Result<TupleSchema, TupleError> {
+        assert!(self.optional.is_empty(), "required elements must come before optional elements");
+        self.required.push(top_level(element.into())?);
+        Ok(self)
+    }
+
+    /// Add an optional element, which may be left off the end of the tuple
+    ///
+    /// # Panics
+    /// If the element is a nested tuple with descending elements (see `try_optional`).
+    pub fn optional<E: Into<ElementSchema>>(self, element: E) -> TupleSchema {
+        self.try_optional(element).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Add an optional element, failing with `TupleError::NestedDescending` as `try_element` does
+    pub fn try_optional<E: Into<ElementSchema>>(mut self, element: E) -> Result<TupleSchema, TupleError> {
+        self.optional.push(top_level(element.into())?);
+        Ok(self)
+    }
+
+    /// Allow any number of further elements matching `element` after the others
+    ///
+    /// # Panics
+    /// If the element is a nested tuple with descending elements (see `try_rest`).
+    pub fn rest<E: Into<ElementSchema>>(self, element: E) -> TupleSchema {
+        self.try_rest(element).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Allow any number of further elements matching `element`, failing with
+    /// `TupleError::NestedDescending` as `try_element` does
+    pub fn try_rest<E: Into<ElementSchema>>(mut self, element: E) -> Result<TupleSchema, TupleError> {
+        self.rest = Some(Box::new(top_level(element.into())?));
+        Ok(self)
+    }
+
+    /// Check that an encoded tuple matches this schema
+    pub fn validate<T: AsRef<[u8]>>(&self, input: T) -> Result<(), TupleError> {
+        self.decode(input.as_ref()).map(|_| ())
+    }
+
+    /// Decode a tuple using the default `DecodeOptions`, checking that it matches this schema
+    ///
+    /// Fails with `TupleError::SchemaMismatch` if an element has the wrong type, or
+    /// `TupleError::ElementCountMismatch` if the tuple (or a nested tuple) has too few or too many
+    /// elements.
+    pub fn decode(&self, input: &[u8]) -> Result<Vec<Segment>, TupleError> {
+        self.decode_with(input, &DecodeOptions::default())
+    }
+
+    /// Decode a tuple, failing if any of the given limits are exceeded or it doesn't match this
+    /// schema
+    pub fn decode_with(&self, input: &[u8], options: &DecodeOptions) -> Result<Vec<Segment>, TupleError> {
+        let segments = if self.has_descending() {
+            descending::decode_elements(input, options, |index, _| {
+                matches!(self.element_schema(index), Some(ElementSchema::Descending(_)))
+            })?
+        } else {
+            options.decode(input)?
+        };
+        self.validate_segments(&segments)?;
+
+        Ok(segments)
+    }
+
+    /// Check that decoded segments match this schema
+    pub fn validate_segments(&self, segments: &[Segment]) -> Result<(), TupleError> {
+        self.check(segments, &mut Vec::new())
+    }
+
+    /// The schema of the element at `index`, if the tuple may have that many elements
+    fn element_schema(&self, index: usize) -> Option<&ElementSchema> {
+        self.required.iter()
+            .chain(self.optional.iter())
+            .nth(index)
+            .or(self.rest.as_deref())
+    }
+
+    fn has_descending(&self) -> bool {
+        self.required.iter()
+            .chain(self.optional.iter())
+            .chain(self.rest.as_deref())
+            .any(|element| matches!(element, ElementSchema::Descending(_)))
+    }
+
+    fn max_len(&self) -> Option<usize> {
+        match self.rest {
+            Some(_) => None,
+            None => Some(self.required.len() + self.optional.len()),
+        }
+    }
+
+    fn check(&self, segments: &[Segment], path: &mut Vec<usize>) -> Result<(), TupleError> {
+        let max = self.max_len();
+        if segments.len() < self.required.len() || max.is_some_and(|max| segments.len() > max) {
+            return Err(TupleError::ElementCountMismatch {
+                path: path.clone(),
+                min: self.required.len(),
+                max,
+                found: segments.len(),
+            });
+        }
+
+        for (index, segment) in segments.iter().enumerate() {
+            let element = self.element_schema(index).expect("element count is checked up front");
+
+            path.push(index);
+            element.check(segment, path)?;
+            path.pop();
+        }
+
+        Ok(())
+    }
+}
+
+impl ElementSchema {
+    fn check(&self, segment: &Segment, path: &mut Vec<usize>) -> Result<(), TupleError> {
+        let expected = match self {
+            ElementSchema::Any => return Ok(()),
+            ElementSchema::Type(segment_type) => *segment_type,
+            ElementSchema::Nested(schema) => match segment {
+                Segment::Nested(inner) => return schema.check(inner, path),
+                _ => SegmentType::Nested,
+            },
+            ElementSchema::Descending(schema) => match segment {
+                Segment::Descending(inner) => return schema.check(inner, path),
+                _ => SegmentType::Descending,
+            },
+        };
+
+        if segment.segment_type() == expected {
+            Ok(())
+        } else {
+            Err(TupleError::SchemaMismatch { path: path.clone(), expected, found: segment.segment_type() })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::{String, ToString};
+    use {Descending, Tuple};
+
+    fn users() -> TupleSchema {
+        TupleSchema::new()
+            .element(SegmentType::String)
+            .element(SegmentType::Integer)
+            .optional(ElementSchema::Any)
+            .optional(TupleSchema::new().element(SegmentType::Boolean).rest(SegmentType::Bytes))
+    }
+
+    #[test]
+    fn test_valid() {
+        let schema = users();
+
+        assert!(schema.validate(::tuple!("users", 1)).is_ok());
+        assert!(schema.validate(::tuple!("users", 1, 2.5f64)).is_ok());
+        assert!(schema.validate(::tuple!("users", 1, "any", vec![Segment::Boolean(true)])).is_ok());
+        assert!(schema.validate(::tuple!("users", 1, "any", vec![Segment::Boolean(true), Segment::Bytes(vec![0])])).is_ok());
+        assert_eq!(schema.decode(::tuple!("users", 1).as_bytes()), ::tuple!("users", 1).as_segments());
+    }
+
+    #[test]
+    fn test_type_mismatch() {
+        let schema = users();
+
+        assert_eq!(schema.validate(::tuple!(1, 1)), Err(TupleError::SchemaMismatch {
+            path: vec![0],
+            expected: SegmentType::String,
+            found: SegmentType::Integer,
+        }));
+        assert_eq!(schema.validate(::tuple!("users", 1, 0, 1)), Err(TupleError::SchemaMismatch {
+            path: vec![3],
+            expected: SegmentType::Nested,
+            found: SegmentType::Integer,
+        }));
+        assert_eq!(schema.validate(::tuple!("users", 1, 0, vec![Segment::Boolean(true), Segment::Integer(2)])), Err(TupleError::SchemaMismatch {
+            path: vec![3, 1],
+            expected: SegmentType::Bytes,
+            found: SegmentType::Integer,
+        }));
+    }
+
+    #[test]
+    fn test_element_count() {
+        let schema = users();
+
+        assert_eq!(schema.validate(::tuple!("users")), Err(TupleError::ElementCountMismatch {
+            path: vec![],
+            min: 2,
+            max: Some(4),
+            found: 1,
+        }));
+        assert_eq!(schema.validate(::tuple!("users", 1, 0, Vec::<Segment>::new())), Err(TupleError::ElementCountMismatch {
+            path: vec![3],
+            min: 1,
+            max: None,
+            found: 0,
+        }));
+        assert!(schema.validate(::tuple!("users", 1, 0, vec![Segment::Boolean(true)], 5)).is_err());
+        assert!(TupleSchema::new().validate(Tuple::new()).is_ok());
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        assert_eq!(users().validate([0x99]), Err(TupleError::DecodeError { position: 0, type_code: 0x99 }));
+
+        let options = DecodeOptions::new().max_elements(1);
+        assert!(users().decode_with(::tuple!("users", 1).as_bytes(), &options).is_err());
+    }
+
+    #[test]
+    fn test_messages() {
+        assert_eq!(users().validate(::tuple!("users", 1, 0, vec![Segment::Integer(2)])).unwrap_err().to_string(), "element 3.0 expected boolean, found integer");
+        assert_eq!(users().validate(::tuple!("users")).unwrap_err().to_string(), "tuple has 1 elements, expected 2 to 4");
+        assert_eq!(users().validate(::tuple!("users", 1, 0, Vec::<Segment>::new())).unwrap_err().to_string(), "nested tuple at element 3 has 0 elements, expected at least 1");
+        assert_eq!(TupleSchema::new().validate(::tuple!(1)).unwrap_err().to_string(), "tuple has 1 elements, expected 0");
+    }
+
+    #[test]
+    fn test_descending() {
+        let schema = TupleSchema::new()
+            .element(SegmentType::String)
+            .rest(ElementSchema::Descending(Box::new(SegmentType::Integer.into())));
+        let key = ::tuple!("scores", Descending(10), Descending(-3));
+
+        assert_eq!(schema.decode(key.as_bytes()), Ok(vec![
+            Segment::String(String::from("scores")),
+            Segment::Descending(Box::new(Segment::Integer(10))),
+            Segment::Descending(Box::new(Segment::Integer(-3))),
+        ]));
+        assert!(schema.decode_with(key.as_bytes(), &DecodeOptions::new().strict(true)).is_ok());
+        assert_eq!(schema.validate(::tuple!("scores", Descending("10"))), Err(TupleError::SchemaMismatch {
+            path: vec![1],
+            expected: SegmentType::Integer,
+            found: SegmentType::String,
+        }));
+        assert_eq!(schema.validate_segments(&::tuple!("scores", 10).as_segments().unwrap()), Err(TupleError::SchemaMismatch {
+            path: vec![1],
+            expected: SegmentType::Descending,
+            found: SegmentType::Integer,
+        }));
+    }
+
+    #[test]
+    fn test_descending_decode_errors() {
+        let schema = TupleSchema::new().element(Descending(SegmentType::String));
+        let key = ::tuple!(Descending("users")).into_bytes();
+
+        assert!(schema.validate(&key[..3]).is_err());
+        assert_eq!(schema.validate(&key[..key.len() - 1]), Err(TupleError::NonCanonical { position: key.len() - 1 }));
+        assert!(schema.decode_with(&key, &DecodeOptions::new().max_bytes(3)).is_err());
+        assert!(TupleSchema::new().element(SegmentType::String).validate(key).is_err());
+    }
+
+    #[test]
+    fn test_try_nested_descending() {
+        let nested = TupleSchema::new().element(Descending(SegmentType::Integer));
+
+        assert_eq!(TupleSchema::new().try_element(nested.clone()), Err(TupleError::NestedDescending));
+        assert_eq!(TupleSchema::new().try_optional(Descending(nested.clone())), Err(TupleError::NestedDescending));
+        assert_eq!(TupleSchema::new().try_rest(nested), Err(TupleError::NestedDescending));
+        assert!(TupleSchema::new().try_element(Descending(TupleSchema::new().element(SegmentType::Integer))).is_ok());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_nested_descending() {
+        let nested = TupleSchema::new().element(Descending(SegmentType::Integer));
+        let _ = TupleSchema::new().element(SegmentType::String).optional(nested);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_required_after_optional() {
+        let _ = TupleSchema::new().optional(SegmentType::Integer).element(SegmentType::Integer);
+    }
+}
@@ -0,0 +1,294 @@
+//! Secondary index maintenance
+//!
+//! An `Index` picks fields out of a record and stores them in keys of the form
+//! `(index_name, indexed_values..., primary_key...)`, so records can be found by those fields with
+//! a range scan. Diffing the old and new versions of a record gives the index entries to set and
+//! clear, which keeps the index consistent with the records.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use descending;
+use errors::TupleError;
+use schema::TupleSchema;
+use segment::Segment;
+use store::{KeyValueStore, StoreError};
+use Tuple;
+
+/// A secondary index over the fields of records
+///
+/// Records are decoded tuples, and fields are identified by their position in the record.
+///
+/// # Examples
+/// ```
+/// #[macro_use] extern crate binary_tuples;
+///
+/// use binary_tuples::secondary_index::{Index, Mutation};
+/// use binary_tuples::segment::Segment;
+///
+/// # fn main() {
+/// // (name, email, age) records, indexed by email
+/// let index = Index::new("by_email", &[1]);
+/// let user = tuple!("users", 7);
+///
+/// let old = tuple!("alice", "alice@example.com", 30).as_segments().unwrap();
+/// let new = tuple!("alice", "alice@example.org", 30).as_segments().unwrap();
+///
+/// assert_eq!(index.mutations(&user, Some(&old), Some(&new)).unwrap(), vec![
+///     Mutation::Clear(tuple!("by_email", "alice@example.com", "users", 7)),
+///     Mutation::Set(tuple!("by_email", "alice@example.org", "users", 7)),
+/// ]);
+///
+/// let entry = index.decode(tuple!("by_email", "alice@example.org", "users", 7).as_bytes()).unwrap();
+/// assert_eq!(entry.primary_key, user);
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Index {
+    name: String,
+    fields: Vec<usize>,
+}
+
+/// A change to the entries of an index
+#[derive(Clone, Debug, PartialEq)]
+pub enum Mutation {
+    /// Add an entry with this key
+    Set(Tuple),
+    /// Remove the entry with this key
+    Clear(Tuple),
+}
+
+/// A decoded index entry
+#[derive(Clone, Debug, PartialEq)]
+pub struct IndexEntry {
+    /// The values of the indexed fields
+    pub values: Vec<Segment>,
+    /// The primary key of the indexed record
+    pub primary_key: Tuple,
+}
+
+impl Index {
+    /// Define an index which stores the given fields of each record, in order
+    pub fn new<N: Into<String>>(name: N, fields: &[usize]) -> Index {
+        Index { name: name.into(), fields: Vec::from(fields) }
+    }
+
+    /// The name of the index, which is the first element of every index key
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The positions of the indexed fields
+    pub fn fields(&self) -> &[usize] {
+        &self.fields
+    }
+
+    /// The prefix shared by every key in this index
+    pub fn prefix(&self) -> Tuple {
+        let mut prefix = Tuple::new();
+        prefix.add_segment(&Segment::String(self.name.clone()));
+        prefix
+    }
+
+    /// The index key for a record
+    ///
+    /// Fails with `TupleError::IndexOutOfBounds` if the record doesn't have one of the indexed
+    /// fields.
+    pub fn key(&self, record: &[Segment], primary_key: &Tuple) -> Result<Tuple, TupleError> {
+        let mut key = self.prefix();
+        for &field in self.fields.iter() {
+            match record.get(field) {
+                Some(value) => key.add_segment(value),
+                None => return Err(TupleError::IndexOutOfBounds { index: field, len: record.len() }),
+            }
+        }
+        key.add_builder(primary_key);
+
+        Ok(key)
+    }
+
+    /// The changes needed to keep the index consistent when a record changes
+    ///
+    /// `old` is `None` when the record is being created and `new` is `None` when it is being
+    /// deleted. Nothing needs to change if none of the indexed fields did.
+    pub fn mutations(&self, primary_key: &Tuple, old: Option<&[Segment]>, new: Option<&[Segment]>) -> Result<Vec<Mutation>, TupleError> {
+        let old = match old {
+            Some(record) => Some(self.key(record, primary_key)?),
+            None => None,
+        };
+        let new = match new {
+            Some(record) => Some(self.key(record, primary_key)?),
+            None => None,
+        };
+
+        Ok(match (old, new) {
+            (Some(old), Some(new)) if old == new => vec![],
+            (old, new) => old.map(Mutation::Clear).into_iter().chain(new.map(Mutation::Set)).collect(),
+        })
+    }
+
+    /// The range of index keys for records whose leading indexed fields have the given values
+    pub fn range(&self, values: &[Segment]) -> (Vec<u8>, Vec<u8>) {
+        let mut prefix = self.prefix();
+        for value in values.iter() {
+            prefix.add_segment(value);
+        }
+
+        prefix.range()
+    }
+
+    /// Decode an index key back into the indexed values and the primary key
+    ///
+    /// Descending elements are picked out by their inverted type codes. Fails with
+    /// `TupleError::PrefixMismatch` if the key belongs to a different index, or
+    /// `TupleError::MissingElement` if it is too short.
+    pub fn decode(&self, key: &[u8]) -> Result<IndexEntry, TupleError> {
+        self.entry(descending::decode(key)?)
+    }
+
+    /// Decode an index key with a schema for the whole key, including the index name
+    ///
+    /// Fails as `decode` does, or if the key doesn't match the schema.
+    pub fn decode_with(&self, key: &[u8], schema: &TupleSchema) -> Result<IndexEntry, TupleError> {
+        self.entry(schema.decode(key)?)
+    }
+
+    fn entry(&self, mut segments: Vec<Segment>) -> Result<IndexEntry, TupleError> {
+
+        match segments.first() {
+            Some(Segment::String(name)) if *name == self.name => {}
+            _ => return Err(TupleError::PrefixMismatch),
+        }
+        if segments.len() < 1 + self.fields.len() {
+            return Err(TupleError::MissingElement);
+        }
+
+        let primary_key = Tuple::from_segments(&segments.split_off(1 + self.fields.len()));
+        segments.remove(0);
+
+        Ok(IndexEntry { values: segments, primary_key })
+    }
+}
+
+/// The changes needed to keep several indexes consistent when a record changes
+pub fn mutations(indexes: &[Index], primary_key: &Tuple, old: Option<&[Segment]>, new: Option<&[Segment]>) -> Result<Vec<Mutation>, TupleError> {
+    let mut mutations = Vec::new();
+    for index in indexes.iter() {
+        mutations.extend(index.mutations(primary_key, old, new)?);
+    }
+
+    Ok(mutations)
+}
+
+impl Mutation {
+    /// The index key being changed
+    pub fn key(&self) -> &Tuple {
+        match self {
+            Mutation::Set(key) | Mutation::Clear(key) => key,
+        }
+    }
+
+    /// Apply the change to a store, where index entries have empty values
+    pub fn apply<S: KeyValueStore>(&self, store: &mut S) -> Result<(), StoreError<S::Error>> {
+        match self {
+            Mutation::Set(key) => store.set(key, &[]),
+            Mutation::Clear(key) => store.clear(key),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::boxed::Box;
+    use schema::ElementSchema;
+    use segment::SegmentType;
+    use store::MemoryStore;
+    use Descending;
+
+    fn record(name: &'static str, email: &'static str, age: i64) -> Vec<Segment> {
+        vec![Segment::Const(name), Segment::Const(email), Segment::Integer(age)]
+    }
+
+    #[test]
+    fn test_key() {
+        let index = Index::new("by_age_name", &[2, 0]);
+        let key = index.key(&record("alice", "a@example.com", 30), &::tuple!(7)).unwrap();
+
+        assert_eq!(key, ::tuple!("by_age_name", 30, "alice", 7));
+        assert_eq!(index.key(&record("alice", "a@example.com", 30)[..2], &::tuple!(7)), Err(TupleError::IndexOutOfBounds { index: 2, len: 2 }));
+    }
+
+    #[test]
+    fn test_mutations() {
+        let index = Index::new("by_age", &[2]);
+        let alice = record("alice", "a@example.com", 30);
+        let older = record("alice", "a@example.com", 31);
+        let renamed = record("alicia", "a@example.com", 30);
+        let pk = ::tuple!(7);
+
+        assert_eq!(index.mutations(&pk, None, Some(&alice)), Ok(vec![Mutation::Set(::tuple!("by_age", 30, 7))]));
+        assert_eq!(index.mutations(&pk, Some(&alice), None), Ok(vec![Mutation::Clear(::tuple!("by_age", 30, 7))]));
+        assert_eq!(index.mutations(&pk, Some(&alice), Some(&renamed)), Ok(vec![]));
+        assert_eq!(index.mutations(&pk, Some(&alice), Some(&older)), Ok(vec![
+            Mutation::Clear(::tuple!("by_age", 30, 7)),
+            Mutation::Set(::tuple!("by_age", 31, 7)),
+        ]));
+        assert_eq!(index.mutations(&pk, None, None), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_decode() {
+        let index = Index::new("by_age_name", &[2, 0]);
+        let entry = index.decode(::tuple!("by_age_name", 30, "alice", "users", 7).as_bytes()).unwrap();
+
+        assert_eq!(entry.values, vec![Segment::Integer(30), Segment::String(String::from("alice"))]);
+        assert_eq!(entry.primary_key, ::tuple!("users", 7));
+
+        assert_eq!(index.decode(::tuple!("by_age", 30, "alice", 7).as_bytes()), Err(TupleError::PrefixMismatch));
+        assert_eq!(index.decode(::tuple!("by_age_name", 30).as_bytes()), Err(TupleError::MissingElement));
+    }
+
+    #[test]
+    fn test_decode_descending() {
+        let index = Index::new("by_age", &[2]);
+        let key = ::tuple!("by_age", Descending(30), "users", 7);
+        let schema = TupleSchema::new()
+            .element(SegmentType::String)
+            .element(Descending(SegmentType::Integer))
+            .rest(ElementSchema::Any);
+
+        let entry = index.decode_with(key.as_bytes(), &schema).unwrap();
+        assert_eq!(entry.values, vec![Segment::Descending(Box::new(Segment::Integer(30)))]);
+        assert_eq!(entry.primary_key, ::tuple!("users", 7));
+
+        assert_eq!(index.decode(key.as_bytes()), Ok(entry));
+        assert_eq!(index.decode_with(::tuple!("by_name", Descending(30)).as_bytes(), &schema), Err(TupleError::PrefixMismatch));
+    }
+
+    #[test]
+    fn test_maintain_store() {
+        let indexes = [Index::new("by_email", &[1]), Index::new("by_age", &[2])];
+        let mut store = MemoryStore::new();
+
+        let records = [(1, record("alice", "a@example.com", 30)), (2, record("bob", "b@example.com", 30))];
+        for (id, record) in records.iter() {
+            for mutation in mutations(&indexes, &::tuple!(*id), None, Some(record)).unwrap() {
+                mutation.apply(&mut store).unwrap();
+            }
+        }
+
+        let updated = record("bob", "bob@example.com", 31);
+        for mutation in mutations(&indexes, &::tuple!(2), Some(&records[1].1), Some(&updated)).unwrap() {
+            mutation.apply(&mut store).unwrap();
+        }
+
+        let (begin, end) = indexes[1].range(&[Segment::Integer(30)]);
+        let thirty = store.scan_raw(&begin, &end).unwrap()
+            .into_iter()
+            .map(|(key, _)| indexes[1].decode(&key).unwrap().primary_key)
+            .collect::<Vec<_>>();
+
+        assert_eq!(thirty, vec![::tuple!(1)]);
+        assert_eq!(store.len(), 4);
+    }
+}
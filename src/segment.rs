@@ -2,8 +2,11 @@ use byteorder::{BigEndian, ByteOrder};
 use constants::*;
 use utils::*;
 use errors::TupleError;
-use std;
-use uuid::Uuid;
+use decode::{DecodeOptions, DecodeState};
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core;
 
 #[derive(Clone, PartialEq, Debug)]
 /// An individual segment within a binary tuple
@@ -19,21 +22,186 @@ pub enum Segment {
     Float(f32),
     Double(f64),
     Boolean(bool),
-    UUID(Uuid),
+    UUID([u8; 16]),
     Tuple(Vec<u8>),
+    /// An element encoded with its bytes inverted, so it sorts in descending order (see
+    /// `descending::Descending`)
+    ///
+    /// Byte strings are written with a second terminating null before being inverted - otherwise
+    /// `"a"` would be a prefix of `"a\0"`, and sort before it at the end of a tuple.
+    ///
+    /// Descending elements can only be decoded with a `TupleSchema`, and only at the top level of
+    /// a tuple - the inverted bytes may contain the terminator of a nested tuple, so encoding a
+    /// `Nested` segment which contains one panics (`Tuple::try_add_segment` checks for this
+    /// instead).
+    Descending(Box<Segment>),
 }
 
-pub(crate) fn encode_slice(input: &[Segment], buffer: &mut Vec<u8>) {
+/// The kind of value held by a segment, used to report type mismatches
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum SegmentType {
+    Bytes,
+    String,
+    Nested,
+    Integer,
+    Float,
+    Double,
+    Boolean,
+    UUID,
+    /// An embedded encoded tuple (see `Segment::Tuple`)
+    Tuple,
+    /// An element in descending order (see `Segment::Descending`)
+    Descending,
+}
+
+impl core::fmt::Display for SegmentType {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let name = match self {
+            SegmentType::Bytes => "bytes",
+            SegmentType::String => "string",
+            SegmentType::Nested => "nested tuple",
+            SegmentType::Integer => "integer",
+            SegmentType::Float => "float",
+            SegmentType::Double => "double",
+            SegmentType::Boolean => "boolean",
+            SegmentType::UUID => "uuid",
+            SegmentType::Tuple => "embedded tuple",
+            SegmentType::Descending => "descending element",
+        };
+
+        f.write_str(name)
+    }
+}
+
+pub(crate) fn encode_slice<O: Output>(input: &[Segment], buffer: &mut O) {
     for segment in input.iter() {
-        segment.encode(buffer)
+        segment.write(buffer)
     }
 }
 
+/// Calculate the number of bytes required to encode a list of segments
+pub fn encoded_len(input: &[Segment]) -> usize {
+    input.iter().map(Segment::encoded_len).sum()
+}
+
+/// Encode a list of segments into a fixed size buffer, returning the number of bytes written
+///
+/// Fails with `TupleError::BufferTooSmall` (without writing anything) if the buffer cannot hold the
+/// encoded segments.
+pub fn encode_slice_into(input: &[Segment], buffer: &mut [u8]) -> Result<usize, TupleError> {
+    let required = encoded_len(input);
+    if required > buffer.len() {
+        return Err(TupleError::BufferTooSmall { required });
+    }
+
+    let mut output = SliceOutput::new(buffer);
+    encode_slice(input, &mut output);
+
+    Ok(output.position())
+}
+
+/// The number of bytes following the type code of a non-zero integer
+fn integer_len(value: i64) -> usize {
+    let magnitude = value.unsigned_abs();
+    let bits = 64 - magnitude.leading_zeros() as usize;
+
+    bits.div_ceil(8)
+}
+
 impl Segment {
-    pub(crate) fn encode(&self, buffer: &mut Vec<u8>) {
+    /// The kind of value held by this segment - constant strings are reported as strings
+    pub fn segment_type(&self) -> SegmentType {
+        match self {
+            Segment::Bytes(_) => SegmentType::Bytes,
+            Segment::String(_) | Segment::Const(_) => SegmentType::String,
+            Segment::Nested(_) => SegmentType::Nested,
+            Segment::Integer(_) => SegmentType::Integer,
+            Segment::Float(_) => SegmentType::Float,
+            Segment::Double(_) => SegmentType::Double,
+            Segment::Boolean(_) => SegmentType::Boolean,
+            Segment::UUID(_) => SegmentType::UUID,
+            Segment::Tuple(_) => SegmentType::Tuple,
+            Segment::Descending(_) => SegmentType::Descending,
+        }
+    }
+
+    /// Whether this is a string or bytes, whose terminating null can be followed by an escape
+    /// Check that no nested tuple within this segment contains a descending element, which can't be
+    /// encoded
+    pub(crate) fn check_nesting(&self) -> Result<(), TupleError> {
+        match self {
+            Segment::Nested(inner) => {
+                for segment in inner.iter() {
+                    if let Segment::Descending(_) = segment {
+                        return Err(TupleError::NestedDescending);
+                    }
+                    segment.check_nesting()?;
+                }
+                Ok(())
+            }
+            Segment::Descending(inner) => inner.check_nesting(),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn is_byte_string(&self) -> bool {
+        matches!(self, Segment::Bytes(_) | Segment::String(_) | Segment::Const(_))
+    }
+
+    /// Encode this segment, appending it to the end of the buffer
+    ///
+    /// # Panics
+    /// If a nested tuple within the segment contains a descending element.
+    pub fn encode(&self, buffer: &mut Vec<u8>) {
+        self.write(buffer)
+    }
+
+    /// Encode this segment into a fixed size buffer, returning the number of bytes written
+    ///
+    /// Fails with `TupleError::BufferTooSmall` (without writing anything) if the buffer cannot hold
+    /// the encoded segment - `encoded_len` can be used to size the buffer up front.
+    ///
+    /// # Examples
+    /// ```
+    /// use binary_tuples::segment::Segment;
+    ///
+    /// let mut buffer = [0; 16];
+    /// let written = Segment::Const("users").encode_into(&mut buffer).unwrap();
+    ///
+    /// assert_eq!(&buffer[..written], &[2, 117, 115, 101, 114, 115, 0]);
+    /// ```
+    pub fn encode_into(&self, buffer: &mut [u8]) -> Result<usize, TupleError> {
+        encode_slice_into(core::slice::from_ref(self), buffer)
+    }
+
+    /// Encode this segment into a `bytes::BufMut`
+    #[cfg(feature = "bytes")]
+    pub fn encode_buf<B: ::bytes::BufMut>(&self, buffer: &mut B) {
+        self.write(&mut BufOutput(buffer))
+    }
+
+    /// Calculate the number of bytes required to encode this segment
+    pub fn encoded_len(&self) -> usize {
+        match self {
+            Segment::Bytes(data) => escaped_len(data),
+            Segment::String(data) => escaped_len(data.as_bytes()),
+            Segment::Const(data) => escaped_len(data.as_bytes()),
+            Segment::Nested(inner) => 2 + encoded_len(inner),
+            Segment::Integer(0) => 1,
+            Segment::Integer(value) => 1 + integer_len(*value),
+            Segment::Float(_) => 5,
+            Segment::Double(_) => 9,
+            Segment::Boolean(_) => 1,
+            Segment::UUID(_) => 17,
+            Segment::Tuple(value) => value.len(),
+            Segment::Descending(inner) => inner.encoded_len() + inner.is_byte_string() as usize,
+        }
+    }
+
+    pub(crate) fn write<O: Output>(&self, buffer: &mut O) {
         match self {
             Segment::Bytes(data) => {
-                encode_byte_string(BYTES_CODE, &data, buffer);
+                encode_byte_string(BYTES_CODE, data, buffer);
             }
             Segment::String(data) => {
                 encode_byte_string(STRING_CODE, data.as_bytes(), buffer);
@@ -42,17 +210,21 @@ impl Segment {
                 encode_byte_string(STRING_CODE, data.as_bytes(), buffer);
             }
             Segment::Nested(inner) => {
+                assert!(
+                    !inner.iter().any(|segment| matches!(segment, Segment::Descending(_))),
+                    "descending elements can't be nested, as their inverted bytes may end the nested tuple early"
+                );
                 buffer.push(NESTED_CODE);
-                encode_slice(&inner, buffer);
+                encode_slice(inner, buffer);
                 buffer.push(NULL)
             }
             Segment::Integer(0) => {
                 buffer.push(INT_ZERO_CODE);
             }
-            Segment::Integer(std::i64::MIN) => {
+            Segment::Integer(i64::MIN) => {
                 buffer.push(INT_ZERO_CODE - 8);
                 let mut buf = [0; 8];
-                BigEndian::write_u64(&mut buf, std::u64::MAX >> 1);
+                BigEndian::write_u64(&mut buf, u64::MAX >> 1);
                 buffer.extend_from_slice(&buf)
             }
             Segment::Integer(value) if *value > 0 => {
@@ -66,11 +238,11 @@ impl Segment {
                 buffer.push(INT_ZERO_CODE + 8 - empty_bytes as u8);
                 buffer.extend_from_slice(&buf[empty_bytes..])
             }
-            Segment::Integer(value) if *value < 0 => {
+            Segment::Integer(value) => {
                 let complement = (-*value) as u64;
 
                 let mut buf = [0; 8];
-                BigEndian::write_u64(&mut buf, complement as u64);
+                BigEndian::write_u64(&mut buf, complement);
 
                 let empty_bytes = buf.iter()
                     .take_while(|v| { **v == 0 })
@@ -85,7 +257,18 @@ impl Segment {
                 buffer.extend_from_slice(&buf[empty_bytes..]);
             }
             Segment::Tuple(value) => {
-                buffer.extend_from_slice(&value);
+                buffer.extend_from_slice(value);
+            }
+            Segment::Descending(inner) => {
+                let mut encoded = Vec::with_capacity(inner.encoded_len());
+                inner.write(&mut encoded);
+                if inner.is_byte_string() {
+                    encoded.push(NULL);
+                }
+                for byte in encoded.iter_mut() {
+                    *byte = !*byte;
+                }
+                buffer.extend_from_slice(&encoded)
             }
             Segment::Boolean(value) => {
                 if *value {
@@ -96,53 +279,59 @@ impl Segment {
             }
             Segment::UUID(value) => {
                 buffer.push(UUID_CODE);
-                buffer.extend_from_slice(value.as_bytes())
+                buffer.extend_from_slice(value)
             }
             Segment::Float(value) => {
                 buffer.reserve(5);
                 buffer.push(FLOAT_CODE);
 
-                buffer.extend_from_slice(&[0, 0, 0, 0]);
-
-                let start = buffer.len() - 4;
-                BigEndian::write_f32(&mut buffer[start..], *value);
-                encode_sortable_float(&mut buffer[start..]);
+                let mut buf = [0; 4];
+                BigEndian::write_f32(&mut buf, *value);
+                encode_sortable_float(&mut buf);
+                buffer.extend_from_slice(&buf);
             }
             Segment::Double(value) => {
                 buffer.reserve(9);
                 buffer.push(DOUBLE_CODE);
 
-                buffer.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0]);
-
-                let start = buffer.len() - 8;
-                BigEndian::write_f64(&mut buffer[start..], *value);
-                encode_sortable_float(&mut buffer[start..]);
+                let mut buf = [0; 8];
+                BigEndian::write_f64(&mut buf, *value);
+                encode_sortable_float(&mut buf);
+                buffer.extend_from_slice(&buf);
             }
-            _ => ()
         }
     }
 
-    fn decode_segments(input: &[u8]) -> Result<(Vec<Segment>, usize), TupleError> {
+    /// Decode the elements starting at `start` up to the end of the input or a terminating null,
+    /// returning the position of the terminator (or the end of the input)
+    pub(crate) fn decode_segments(input: &[u8], start: usize, depth: usize, state: &mut DecodeState) -> Result<(Vec<Segment>, usize), TupleError> {
         let mut segments = Vec::new();
 
-        let mut index = 0;
+        let mut index = start;
 
         while index < input.len() {
+            if input[index] != NULL {
+                state.elements += 1;
+                state.options.check_elements(state.elements, index)?;
+            }
+
             index += match input[index] {
                 BYTES_CODE => {
-                    let (read, result) = decode_byte_string(&input[index + 1..]);
+                    let (read, result) = decode_byte_string(&input[index + 1..])
+                        .ok_or(TupleError::TruncatedTuple)?;
                     segments.push(Segment::Bytes(result));
 
                     read + 1
                 }
                 STRING_CODE => {
-                    let (read, result) = decode_byte_string(&input[index + 1..]);
-                    let result = std::string::String::from_utf8(result)?;
+                    let (read, result) = decode_byte_string(&input[index + 1..])
+                        .ok_or(TupleError::TruncatedTuple)?;
+                    let result = String::from_utf8(result)?;
                     segments.push(Segment::String(result));
 
                     read + 1
                 }
-                INT_NEG_MIN_CODE ... INT_NEG_MAX_CODE => {
+                INT_NEG_MIN_CODE ..= INT_NEG_MAX_CODE => {
                     let bytes = (INT_ZERO_CODE - input[index]) as usize;
                     let mut buf = [0; 8];
 
@@ -156,8 +345,8 @@ impl Segment {
 
                     let twos_complement = BigEndian::read_u64(&buf) as i64;
 
-                    let value = if twos_complement == std::i64::MAX {
-                        std::i64::MIN
+                    let value = if twos_complement == i64::MAX {
+                        i64::MIN
                     } else {
                         twos_complement - SIZE_LIMITS[bytes] as i64
                     };
@@ -166,7 +355,7 @@ impl Segment {
 
                     bytes + 1
                 }
-                INT_POS_MIN_CODE ... INT_POS_MAX_CODE => {
+                INT_POS_MIN_CODE ..= INT_POS_MAX_CODE => {
                     let bytes = (input[index] - INT_ZERO_CODE) as usize;
                     let mut buf = [0; 8];
 
@@ -197,8 +386,8 @@ impl Segment {
                     let mut float = [
                         input[index + 1],
                         input[index + 2],
-                        input[index + 2],
-                        input[index + 3]
+                        input[index + 3],
+                        input[index + 4]
                     ];
                     decode_sortable_float(&mut float);
                     segments.push(Segment::Float(BigEndian::read_f32(&float)));
@@ -206,19 +395,19 @@ impl Segment {
                     5
                 }
                 DOUBLE_CODE => {
-                    if index + 5 > input.len() {
+                    if index + 9 > input.len() {
                         return Err(TupleError::DecimalDecodeError{ position: index })
                     }
 
                     let mut float = [
                         input[index + 1],
                         input[index + 2],
-                        input[index + 2],
                         input[index + 3],
                         input[index + 4],
                         input[index + 5],
                         input[index + 6],
                         input[index + 7],
+                        input[index + 8],
                     ];
                     decode_sortable_float(&mut float);
                     segments.push(Segment::Double(BigEndian::read_f64(&float)));
@@ -236,23 +425,27 @@ impl Segment {
                     1
                 }
                 UUID_CODE => {
-                    match Uuid::from_bytes(&input[index + 1..index + 17]) {
-                        Ok(uuid) => Ok(segments.push(Segment::UUID(uuid))),
-                        Err(_) => Err(TupleError::UuidDecodeError {position: index})
-                    }?;
+                    if index + 17 > input.len() {
+                        return Err(TupleError::UuidDecodeError { position: index })
+                    }
+
+                    let mut uuid = [0; 16];
+                    uuid.copy_from_slice(&input[index + 1..index + 17]);
+                    segments.push(Segment::UUID(uuid));
 
                     17
                 }
                 NESTED_CODE => {
-                    let (result, read) = Segment::decode_segments(&input[index + 1..])?;
+                    state.options.check_depth(depth + 1, index)?;
+                    let (result, end) = Segment::decode_segments(input, index + 1, depth + 1, state)?;
 
                     segments.push(Segment::Nested(result));
 
-                    if input[index + read + 1] != NULL {
+                    if input.get(end) != Some(&NULL) {
                         return Err(TupleError::TruncatedNestedTuple);
                     }
 
-                    read + 2
+                    end + 1 - index
                 }
                 NULL => {
                     return Ok((segments, index))
@@ -261,24 +454,20 @@ impl Segment {
             }
         }
 
-        return Ok((segments, index))
+        Ok((segments, index))
     }
 
+    /// Decode a tuple using the default `DecodeOptions`
     pub(crate) fn decode(input: &[u8]) -> Result<Vec<Segment>, TupleError> {
-        let ( segments, read ) = Segment::decode_segments(input)?;
-
-        if read != input.len() {
-            Err(TupleError::TruncatedTuple)
-        } else {
-            Ok(segments)
-        }
+        DecodeOptions::default().decode(input)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use uuid::UuidVersion;
+    #[cfg(feature = "uuid")]
+    use uuid::Uuid;
 
     fn encode(segment: Segment) -> Vec<u8> {
         let mut buffer = Vec::new();
@@ -293,6 +482,55 @@ mod tests {
         value
     }
 
+    #[test]
+    fn test_encoded_len() {
+        let segments = vec![
+            Segment::Bytes(vec![0, 1, 0]),
+            Segment::Const("wow"),
+            Segment::Nested(vec![Segment::Integer(0), Segment::Boolean(true)]),
+            Segment::Integer(1),
+            Segment::Integer(-256),
+            Segment::Integer(i64::MAX),
+            Segment::Integer(i64::MIN),
+            Segment::Integer(i64::MIN + 1),
+            Segment::Float(1.0),
+            Segment::Double(1.0),
+            Segment::UUID([0; 16]),
+            Segment::Tuple(vec![INT_ZERO_CODE, TRUE_CODE]),
+        ];
+
+        for segment in segments.iter() {
+            assert_eq!(segment.encoded_len(), encode(segment.clone()).len(), "{:?}", segment);
+        }
+    }
+
+    #[test]
+    fn test_encode_into() {
+        let segment = Segment::Bytes(vec![1, 2, 0, 3, 4]);
+        let mut buffer = [0; 10];
+
+        assert_eq!(segment.encode_into(&mut buffer), Ok(8));
+        assert_eq!(&buffer[..8], &[BYTES_CODE, 1, 2, 0, 255, 3, 4, 0]);
+    }
+
+    #[test]
+    fn test_encode_into_too_small() {
+        let segment = Segment::Const("wow");
+        let mut buffer = [0; 4];
+
+        assert_eq!(segment.encode_into(&mut buffer), Err(TupleError::BufferTooSmall { required: 5 }));
+        assert_eq!(buffer, [0; 4]);
+    }
+
+    #[test]
+    fn test_encode_slice_into() {
+        let segments = vec![Segment::Const("a"), Segment::Integer(-1)];
+        let mut buffer = [0; 5];
+
+        assert_eq!(encode_slice_into(&segments, &mut buffer), Ok(5));
+        assert_eq!(buffer, [STRING_CODE, 97, 0, INT_ZERO_CODE - 1, 254]);
+    }
+
     #[test]
     fn test_encode_integer() {
         let result = encode(Segment::Integer(1));
@@ -309,7 +547,7 @@ mod tests {
 
     #[test]
     fn test_encode_max_integer() {
-        let result = encode(Segment::Integer(std::i64::MAX));
+        let result = encode(Segment::Integer(i64::MAX));
 
         assert_eq!(result, vec![INT_ZERO_CODE + 8, 127, 255, 255, 255, 255, 255, 255, 255])
     }
@@ -330,23 +568,23 @@ mod tests {
 
     #[test]
     fn test_encode_min_integer() {
-        let result = encode(Segment::Integer(std::i64::MIN));
+        let result = encode(Segment::Integer(i64::MIN));
 
         assert_eq!(result, vec![INT_ZERO_CODE - 8, 127, 255, 255, 255, 255, 255, 255, 255])
     }
 
     #[test]
     fn test_encode_sort_integer() {
-        let max = encode(Segment::Integer(std::i64::MAX));
+        let max = encode(Segment::Integer(i64::MAX));
         let p257 = encode(Segment::Integer(256));
         let p256 = encode(Segment::Integer(256));
         let p1 = encode(Segment::Integer(1));
-        let _0 = encode(Segment::Integer(0));
+        let zero = encode(Segment::Integer(0));
         let n1 = encode(Segment::Integer(-1));
         let n255 = encode(Segment::Integer(-256));
         let n256 = encode(Segment::Integer(-256));
-        let min_p1 = encode(Segment::Integer(std::i64::MIN + 1));
-        let min = encode(Segment::Integer(std::i64::MIN));
+        let min_p1 = encode(Segment::Integer(i64::MIN + 1));
+        let min = encode(Segment::Integer(i64::MIN));
 
         let input = vec![
             min,
@@ -354,7 +592,7 @@ mod tests {
             n256,
             n255,
             n1,
-            _0,
+            zero,
             p1,
             p256,
             p257,
@@ -434,22 +672,22 @@ mod tests {
 
     #[test]
     fn test_encode_sort_float() {
-        let p_inf = encode(Segment::Float(std::f32::INFINITY));
-        let p_max = encode(Segment::Float(std::f32::MAX));
+        let p_inf = encode(Segment::Float(f32::INFINITY));
+        let p_max = encode(Segment::Float(f32::MAX));
         let p1 = encode(Segment::Float(1.0));
-        let p_min = encode(Segment::Float(std::f32::MIN_POSITIVE));
-        let _0 = encode(Segment::Float(0.0));
-        let n_min = encode(Segment::Float(-std::f32::MIN_POSITIVE));
+        let p_min = encode(Segment::Float(f32::MIN_POSITIVE));
+        let zero = encode(Segment::Float(0.0));
+        let n_min = encode(Segment::Float(-f32::MIN_POSITIVE));
         let n1 = encode(Segment::Float(-1.0));
-        let n_max = encode(Segment::Float(-std::f32::MAX));
-        let n_inf = encode(Segment::Float(std::f32::NEG_INFINITY));
+        let n_max = encode(Segment::Float(-f32::MAX));
+        let n_inf = encode(Segment::Float(f32::NEG_INFINITY));
 
         let input = vec![
             n_inf,
             n_max,
             n1,
             n_min,
-            _0,
+            zero,
             p_min,
             p1,
             p_max,
@@ -479,50 +717,50 @@ mod tests {
 
     #[test]
     fn encode_max_double() {
-        let builder = encode(Segment::Double(std::f64::MAX));
+        let builder = encode(Segment::Double(f64::MAX));
 
         assert_eq!(builder, vec![DOUBLE_CODE, 255, 239, 255, 255, 255, 255, 255, 255]);
     }
 
     #[test]
     fn encode_min_positive_double() {
-        let builder = encode(Segment::Double(std::f64::MIN_POSITIVE));
+        let builder = encode(Segment::Double(f64::MIN_POSITIVE));
 
         assert_eq!(builder, vec![DOUBLE_CODE, 128, 16, 0, 0, 0, 0, 0, 0]);
     }
 
     #[test]
     fn encode_inf_double() {
-        let builder = encode(Segment::Double(std::f64::INFINITY));
+        let builder = encode(Segment::Double(f64::INFINITY));
 
         assert_eq!(builder, vec![DOUBLE_CODE, 255, 240, 0, 0, 0, 0, 0, 0]);
     }
 
     #[test]
     fn encode_neg_inf_double() {
-        let builder = encode(Segment::Double(std::f64::NEG_INFINITY));
+        let builder = encode(Segment::Double(f64::NEG_INFINITY));
 
         assert_eq!(builder, vec![DOUBLE_CODE, 0, 15, 255, 255, 255, 255, 255, 255]);
     }
 
     #[test]
     fn test_encode_sort_double() {
-        let p_inf = encode(Segment::Double(std::f64::INFINITY));
-        let p_max = encode(Segment::Double(std::f64::MAX));
+        let p_inf = encode(Segment::Double(f64::INFINITY));
+        let p_max = encode(Segment::Double(f64::MAX));
         let p1 = encode(Segment::Double(1.0));
-        let p_min = encode(Segment::Double(std::f64::MIN_POSITIVE));
-        let _0 = encode(Segment::Double(0.0));
-        let n_min = encode(Segment::Double(-std::f64::MIN_POSITIVE));
+        let p_min = encode(Segment::Double(f64::MIN_POSITIVE));
+        let zero = encode(Segment::Double(0.0));
+        let n_min = encode(Segment::Double(-f64::MIN_POSITIVE));
         let n1 = encode(Segment::Double(-1.0));
-        let n_max = encode(Segment::Double(-std::f64::MAX));
-        let n_inf = encode(Segment::Double(std::f64::NEG_INFINITY));
+        let n_max = encode(Segment::Double(-f64::MAX));
+        let n_inf = encode(Segment::Double(f64::NEG_INFINITY));
 
         let input = vec![
             n_inf,
             n_max,
             n1,
             n_min,
-            _0,
+            zero,
             p_min,
             p1,
             p_max,
@@ -539,168 +777,183 @@ mod tests {
 
     #[test]
     fn decode_string() {
-        let result = decode(&vec![STRING_CODE, 119, 111, 119, 0]);
+        let result = decode(&[STRING_CODE, 119, 111, 119, 0]);
 
         assert_eq!(result, Segment::String(String::from("wow")));
     }
 
     #[test]
     fn decode_string_escaped() {
-        let result = decode(&vec![STRING_CODE, 119, 111, 119, 0, 255, 0]);
+        let result = decode(&[STRING_CODE, 119, 111, 119, 0, 255, 0]);
 
         assert_eq!(result, Segment::String(String::from("wow\0")));
     }
 
     #[test]
     fn decode_string_start() {
-        let result = decode(&vec![STRING_CODE, 0, 255, 119, 0, 255, 0]);
+        let result = decode(&[STRING_CODE, 0, 255, 119, 0, 255, 0]);
 
         assert_eq!(result, Segment::String(String::from("\0w\0")));
     }
 
     #[test]
     fn decode_bytes() {
-        let result = decode(&vec![BYTES_CODE, 1, 2, 3, 4, 0]);
+        let result = decode(&[BYTES_CODE, 1, 2, 3, 4, 0]);
 
         assert_eq!(result, Segment::Bytes(vec![1, 2, 3, 4]));
     }
 
     #[test]
     fn decode_bytes_escaped() {
-        let result = decode(&vec![BYTES_CODE, 1, 2, 0, 255, 3, 4, 0]);
+        let result = decode(&[BYTES_CODE, 1, 2, 0, 255, 3, 4, 0]);
 
         assert_eq!(result, Segment::Bytes(vec![1, 2, 0, 3, 4]));
     }
 
     #[test]
     fn decode_int_zero() {
-        let result = decode(&vec![INT_ZERO_CODE]);
+        let result = decode(&[INT_ZERO_CODE]);
 
         assert_eq!(result, Segment::Integer(0));
     }
 
     #[test]
     fn decode_float() {
-        let result = decode(&vec![FLOAT_CODE, 191, 128, 0, 0]);
+        let result = decode(&[FLOAT_CODE, 191, 128, 0, 0]);
 
-        assert_eq!(result, Segment::Float(1.0039063));
+        assert_eq!(result, Segment::Float(1.0));
     }
 
     #[test]
     fn decode_larger_float() {
-        let result = decode(&vec![FLOAT_CODE, 192, 0, 0, 0]);
+        let result = decode(&[FLOAT_CODE, 192, 0, 0, 0]);
 
         assert_eq!(result, Segment::Float(2.0));
     }
 
     #[test]
     fn decode_even_larger_float() {
-        let result = decode(&vec![FLOAT_CODE, 198, 245, 111, 7]);
+        let result = decode(&[FLOAT_CODE, 198, 245, 111, 7]);
 
-        assert_eq!(result, Segment::Float(31482.717));
+        assert_eq!(result, Segment::Float(31415.514));
     }
 
     #[test]
     fn decode_double() {
-        let result = decode(&vec![DOUBLE_CODE, 191, 240, 0, 0, 0, 0, 0, 0]);
+        let result = decode(&[DOUBLE_CODE, 191, 240, 0, 0, 0, 0, 0, 0]);
 
-        assert_eq!(result, Segment::Double(1.05859375));
+        assert_eq!(result, Segment::Double(1.0));
     }
 
     #[test]
     fn decode_larger_double() {
-        let result = decode(&vec![DOUBLE_CODE, 192, 0, 0, 0, 0, 0, 0, 0]);
+        let result = decode(&[DOUBLE_CODE, 192, 0, 0, 0, 0, 0, 0, 0]);
 
         assert_eq!(result, Segment::Double(2.0));
     }
 
     #[test]
     fn decode_even_larger_double() {
-        let result = decode(&vec![DOUBLE_CODE, 192, 222, 173, 224, 229, 96, 65, 137]);
+        let result = decode(&[DOUBLE_CODE, 192, 222, 173, 224, 229, 96, 65, 137]);
 
-        assert_eq!(result, Segment::Double(31610.716851562498));
+        assert_eq!(result, Segment::Double(31415.514));
     }
 
     #[test]
     fn test_decode_integer() {
-        let result = decode(&vec![INT_ZERO_CODE + 1, 1]);
+        let result = decode(&[INT_ZERO_CODE + 1, 1]);
 
         assert_eq!(result, Segment::Integer(1));
     }
 
     #[test]
     fn test_decode_large_integer() {
-        let result = decode(&vec![INT_ZERO_CODE + 2, 19, 136]);
+        let result = decode(&[INT_ZERO_CODE + 2, 19, 136]);
 
         assert_eq!(result, Segment::Integer(5000));
     }
 
     #[test]
     fn test_decode_larger_integer() {
-        let result = decode(&vec![INT_ZERO_CODE + 2, 1, 1]);
+        let result = decode(&[INT_ZERO_CODE + 2, 1, 1]);
 
         assert_eq!(result, Segment::Integer(257));
     }
 
     #[test]
     fn test_decode_neg_integer() {
-        let result = decode(&vec![INT_ZERO_CODE - 1, 254]);
+        let result = decode(&[INT_ZERO_CODE - 1, 254]);
 
         assert_eq!(result, Segment::Integer(-1));
     }
 
     #[test]
     fn test_decode_neg_one_integer() {
-        let result = decode(&vec![INT_ZERO_CODE - 1, 1]);
+        let result = decode(&[INT_ZERO_CODE - 1, 1]);
 
         assert_eq!(result, Segment::Integer(-254));
     }
 
     #[test]
     fn test_decode_neg_malformed() {
-        let result = Segment::decode(&vec![INT_ZERO_CODE - 1]).unwrap_err();
+        let result = Segment::decode(&[INT_ZERO_CODE - 1]).unwrap_err();
 
         assert_eq!(result, TupleError::IntegerDecodeError { position: 0 });
     }
 
     #[test]
     fn test_decode_pos_malformed() {
-        let result = Segment::decode(&vec![INT_ZERO_CODE + 1]).unwrap_err();
+        let result = Segment::decode(&[INT_ZERO_CODE + 1]).unwrap_err();
 
         assert_eq!(result, TupleError::IntegerDecodeError { position: 0 });
     }
 
     #[test]
     fn test_decode_float_malformed() {
-        let result = Segment::decode(&vec![FLOAT_CODE]).unwrap_err();
+        let result = Segment::decode(&[FLOAT_CODE]).unwrap_err();
 
         assert_eq!(result, TupleError::DecimalDecodeError { position: 0 });
     }
 
     #[test]
     fn test_decode_decimal_malformed() {
-        let result = Segment::decode(&vec![DOUBLE_CODE]).unwrap_err();
+        let result = Segment::decode(&[DOUBLE_CODE]).unwrap_err();
+
+        assert_eq!(result, TupleError::DecimalDecodeError { position: 0 });
+    }
+
+    #[test]
+    fn test_decode_unterminated_byte_string() {
+        assert_eq!(Segment::decode(&[BYTES_CODE]), Err(TupleError::TruncatedTuple));
+        assert_eq!(Segment::decode(&[STRING_CODE, b'a']), Err(TupleError::TruncatedTuple));
+        assert_eq!(Segment::decode(&[BYTES_CODE, 1, NULL, NULL_ESCAPE]), Err(TupleError::TruncatedTuple));
+        assert_eq!(DecodeOptions::new().decode(&[STRING_CODE, b'a']), Err(TupleError::TruncatedTuple));
+    }
+
+    #[test]
+    fn test_decode_truncated_decimal() {
+        let result = Segment::decode(&[DOUBLE_CODE, 192, 0, 0, 0, 0]).unwrap_err();
 
         assert_eq!(result, TupleError::DecimalDecodeError { position: 0 });
     }
 
     #[test]
     fn test_decode_max_integer() {
-        let result = decode(&vec![INT_ZERO_CODE + 8, 127, 255, 255, 255, 255, 255, 255, 255]);
+        let result = decode(&[INT_ZERO_CODE + 8, 127, 255, 255, 255, 255, 255, 255, 255]);
 
-        assert_eq!(result, Segment::Integer(std::i64::MAX));
+        assert_eq!(result, Segment::Integer(i64::MAX));
     }
 
     #[test]
     fn test_decode_max_neg_integer() {
-        let result = decode(&vec![INT_ZERO_CODE - 8, 127, 255, 255, 255, 255, 255, 255, 255]);
+        let result = decode(&[INT_ZERO_CODE - 8, 127, 255, 255, 255, 255, 255, 255, 255]);
 
-        assert_eq!(result, Segment::Integer(std::i64::MIN));
+        assert_eq!(result, Segment::Integer(i64::MIN));
     }
 
     #[test]
     fn test_decode_neg_boundary_integer() {
-        let result = decode(&vec![INT_ZERO_CODE - 2, 254, 255]);
+        let result = decode(&[INT_ZERO_CODE - 2, 254, 255]);
 
         assert_eq!(result, Segment::Integer(-256));
     }
@@ -721,22 +974,23 @@ mod tests {
 
     #[test]
     fn test_decode_boolen_true() {
-        let result = decode(&vec![TRUE_CODE]);
+        let result = decode(&[TRUE_CODE]);
 
         assert_eq!(result, Segment::Boolean(true));
     }
 
     #[test]
     fn test_decode_boolen_false() {
-        let result = decode(&vec![FALSE_CODE]);
+        let result = decode(&[FALSE_CODE]);
 
         assert_eq!(result, Segment::Boolean(false));
     }
 
     #[test]
+    #[cfg(feature = "uuid")]
     fn test_encode_uuid() {
-        let uuid = Uuid::new(UuidVersion::Random).unwrap();
-        let builder = encode(Segment::UUID(uuid));
+        let uuid = Uuid::new_v4();
+        let builder = encode(Segment::UUID(*uuid.as_bytes()));
 
         let mut expected_result = vec![UUID_CODE];
         expected_result.extend_from_slice(uuid.as_bytes());
@@ -747,9 +1001,39 @@ mod tests {
     fn test_decode_uuid() {
         let input = vec![48, 197, 194, 162, 128, 228, 124, 65, 129, 148, 179, 194, 60, 213, 250, 237, 232];
         let result = decode(&input);
-        let uuid_bytes = &input[1..];
 
-        assert_eq!(result, Segment::UUID(Uuid::from_bytes(uuid_bytes).unwrap()));
+        assert_eq!(result, Segment::UUID([197, 194, 162, 128, 228, 124, 65, 129, 148, 179, 194, 60, 213, 250, 237, 232]));
+    }
+
+    #[test]
+    fn test_decode_truncated_nested() {
+        assert_eq!(Segment::decode(&[NESTED_CODE, INT_ZERO_CODE]), Err(TupleError::TruncatedNestedTuple));
+        assert_eq!(Segment::decode(&[NESTED_CODE]), Err(TupleError::TruncatedNestedTuple));
+    }
+
+    #[test]
+    fn test_decode_truncated_uuid() {
+        let result = Segment::decode(&[UUID_CODE, 1, 2, 3]);
+
+        assert_eq!(result, Err(TupleError::UuidDecodeError { position: 0 }));
+    }
+
+    #[test]
+    fn test_check_nesting() {
+        let descending = Segment::Descending(Box::new(Segment::Integer(1)));
+
+        assert_eq!(descending.check_nesting(), Ok(()));
+        assert_eq!(Segment::Nested(vec![Segment::Integer(1), descending.clone()]).check_nesting(), Err(TupleError::NestedDescending));
+        assert_eq!(Segment::Nested(vec![Segment::Nested(vec![descending.clone()])]).check_nesting(), Err(TupleError::NestedDescending));
+        assert_eq!(Segment::Descending(Box::new(Segment::Nested(vec![descending]))).check_nesting(), Err(TupleError::NestedDescending));
+        assert_eq!(Segment::Descending(Box::new(Segment::Nested(vec![Segment::Integer(1)]))).check_nesting(), Ok(()));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_encode_nested_descending() {
+        let mut buffer = Vec::new();
+        Segment::Nested(vec![Segment::Descending(Box::new(Segment::Bytes(vec![1])))]).encode(&mut buffer);
     }
 
     #[test]
@@ -774,14 +1058,14 @@ mod tests {
 
     #[test]
     fn test_decode_nested() {
-        let result = decode(&vec![NESTED_CODE, STRING_CODE, 72, 101, 108, 108, 111, NULL, TRUE_CODE, NULL]);
+        let result = decode(&[NESTED_CODE, STRING_CODE, 72, 101, 108, 108, 111, NULL, TRUE_CODE, NULL]);
 
         assert_eq!(result, Segment::Nested(vec![Segment::String(String::from("Hello")), Segment::Boolean(true)]))
     }
 
     #[test]
     fn test_decode_recursive_nested() {
-        let builder = decode(&vec![NESTED_CODE, NESTED_CODE, TRUE_CODE, STRING_CODE, 72, 101, 108, 108, 111, NULL, NULL, INT_ZERO_CODE + 2, 19, 136, NULL]);
+        let builder = decode(&[NESTED_CODE, NESTED_CODE, TRUE_CODE, STRING_CODE, 72, 101, 108, 108, 111, NULL, NULL, INT_ZERO_CODE + 2, 19, 136, NULL]);
 
         assert_eq!(builder, Segment::Nested(vec![
             Segment::Nested(vec![
@@ -0,0 +1,106 @@
+//! A `KeyValueStore` backed by an LMDB database, using heed
+
+use alloc::vec::Vec;
+use core::ops::Bound;
+use heed::types::Bytes;
+use heed::{Database, Env, Error};
+use super::{BatchOp, KeyValueStore, RawEntries};
+
+/// A store which keeps its entries in an LMDB database
+///
+/// Every operation runs in its own transaction, so each write is committed before it returns. A
+/// batch is written in a single transaction.
+#[derive(Clone, Debug)]
+pub struct LmdbStore {
+    env: Env,
+    database: Database<Bytes, Bytes>,
+}
+
+impl LmdbStore {
+    /// Store entries in the unnamed database of an environment, creating it if needed
+    ///
+    /// Opening the environment is left to the caller, as heed requires it to be opened once per
+    /// process.
+    pub fn new(env: Env) -> Result<LmdbStore, Error> {
+        let mut txn = env.write_txn()?;
+        let database = env.create_database(&mut txn, None)?;
+        txn.commit()?;
+
+        Ok(LmdbStore { env, database })
+    }
+
+    /// Store entries in an existing database
+    pub fn with_database(env: Env, database: Database<Bytes, Bytes>) -> LmdbStore {
+        LmdbStore { env, database }
+    }
+
+    /// The environment which holds the database
+    pub fn env(&self) -> &Env {
+        &self.env
+    }
+}
+
+impl KeyValueStore for LmdbStore {
+    type Error = Error;
+
+    fn get_raw(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+        let txn = self.env.read_txn()?;
+        Ok(self.database.get(&txn, key)?.map(Vec::from))
+    }
+
+    fn set_raw(&mut self, key: &[u8], value: &[u8]) -> Result<(), Error> {
+        let mut txn = self.env.write_txn()?;
+        self.database.put(&mut txn, key, value)?;
+        txn.commit()
+    }
+
+    fn clear_raw(&mut self, key: &[u8]) -> Result<(), Error> {
+        let mut txn = self.env.write_txn()?;
+        self.database.delete(&mut txn, key)?;
+        txn.commit()
+    }
+
+    fn scan_raw(&self, begin: &[u8], end: &[u8]) -> Result<RawEntries, Error> {
+        let txn = self.env.read_txn()?;
+        // LMDB rejects empty keys, which would otherwise be used to scan from the first entry
+        let start = if begin.is_empty() { Bound::Unbounded } else { Bound::Included(begin) };
+        let range = (start, Bound::Excluded(end));
+
+        let entries = self.database.range(&txn, &range)?
+            .map(|entry| entry.map(|(key, value)| (Vec::from(key), Vec::from(value))))
+            .collect();
+        entries
+    }
+
+    fn write_batch(&mut self, batch: &[BatchOp]) -> Result<(), Error> {
+        let mut txn = self.env.write_txn()?;
+        for op in batch.iter() {
+            match op {
+                BatchOp::Set(key, value) => self.database.put(&mut txn, key, value)?,
+                BatchOp::Clear(key) => {
+                    self.database.delete(&mut txn, key)?;
+                }
+            }
+        }
+        txn.commit()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use heed::EnvOpenOptions;
+    use std::{env, fs, process};
+    use store::tests::check_store;
+
+    #[test]
+    fn test_lmdb_store() {
+        let path = env::temp_dir().join(format!("binary-tuples-lmdb-{}", process::id()));
+        fs::create_dir_all(&path).unwrap();
+
+        let env = unsafe { EnvOpenOptions::new().map_size(1 << 20).open(&path).unwrap() };
+        check_store(LmdbStore::new(env).unwrap());
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
@@ -0,0 +1,239 @@
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use descending;
+use pack::{FromTuple, TuplePack};
+use schema::TupleSchema;
+use super::{KeyValueStore, MemoryStore, StoreError};
+use Tuple;
+
+/// A map with typed keys and values, stored as tuples in an ordered key value store
+///
+/// Keys are packed after an optional prefix, so several maps can share a store, and values are
+/// packed as tuples of their own. Scans by a partial key return the entries in key order, and
+/// `with_key_schema` checks the keys they read against a schema.
+///
+/// # Examples
+/// ```
+/// use binary_tuples::store::TupleMap;
+///
+/// let mut posts = TupleMap::<(String, i64), (String,)>::in_memory();
+/// posts.insert(&(String::from("acme"), 1), &(String::from("hello"),)).unwrap();
+/// posts.insert(&(String::from("acme"), 2), &(String::from("world"),)).unwrap();
+/// posts.insert(&(String::from("initech"), 1), &(String::from("tps"),)).unwrap();
+///
+/// let acme = posts.scan_prefix(&("acme",)).unwrap();
+///
+/// assert_eq!(acme.len(), 2);
+/// assert_eq!(acme[1].0, (String::from("acme"), 2));
+/// assert_eq!(posts.get(&(String::from("initech"), 1)).unwrap(), Some((String::from("tps"),)));
+/// ```
+pub struct TupleMap<K, V, S = MemoryStore> {
+    store: S,
+    prefix: Tuple,
+    key_schema: Option<TupleSchema>,
+    marker: PhantomData<(K, V)>,
+}
+
+impl<K: TuplePack + FromTuple, V: TuplePack + FromTuple> TupleMap<K, V, MemoryStore> {
+    /// Create a map backed by a new `MemoryStore`
+    pub fn in_memory() -> Self {
+        TupleMap::new(MemoryStore::new())
+    }
+}
+
+impl<K: TuplePack + FromTuple, V: TuplePack + FromTuple, S: KeyValueStore> TupleMap<K, V, S> {
+    /// Create a map which stores its entries directly in `store`
+    pub fn new(store: S) -> Self {
+        TupleMap::with_prefix(store, Tuple::new())
+    }
+
+    /// Create a map which stores its entries under `prefix` in `store`
+    pub fn with_prefix(store: S, prefix: Tuple) -> Self {
+        TupleMap { store, prefix, key_schema: None, marker: PhantomData }
+    }
+
+    /// Decode the keys read by scans with `schema`, which describes the keys without the prefix
+    pub fn with_key_schema(mut self, schema: TupleSchema) -> Self {
+        self.key_schema = Some(schema);
+        self
+    }
+
+    /// The backing store
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Return the backing store
+    pub fn into_store(self) -> S {
+        self.store
+    }
+
+    /// Store a value under a key, replacing any existing value
+    pub fn insert(&mut self, key: &K, value: &V) -> Result<(), StoreError<S::Error>> {
+        let key = self.key(key);
+        self.store.set(&key, value.pack().as_bytes())
+    }
+
+    /// Read the value stored under a key
+    pub fn get(&self, key: &K) -> Result<Option<V>, StoreError<S::Error>> {
+        match self.store.get(&self.key(key))? {
+            Some(value) => Ok(Some(V::from_segments(descending::decode(&value)?)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Remove the value stored under a key, returning it if there was one
+    ///
+    /// The key is removed even if its value can't be unpacked, in which case the unpacking error is
+    /// returned afterwards. Reading and clearing the value are separate store operations, so a
+    /// concurrent write to the key in between is lost.
+    pub fn remove(&mut self, key: &K) -> Result<Option<V>, StoreError<S::Error>> {
+        let key = self.key(key);
+        let value = self.store.get(&key)?;
+        if value.is_some() {
+            self.store.clear(&key)?;
+        }
+
+        match value {
+            Some(value) => Ok(Some(V::from_segments(descending::decode(&value)?)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Read the entries whose keys start with the elements of `prefix`, in key order
+    ///
+    /// The prefix is any packable value - usually a Rust tuple holding the first few elements of
+    /// the key, such as `(tenant,)` for keys of type `(String, i64)`.
+    pub fn scan_prefix<P: TuplePack + ?Sized>(&self, prefix: &P) -> Result<Vec<(K, V)>, StoreError<S::Error>> {
+        let mut begin = self.prefix.clone();
+        prefix.pack_into(&mut begin);
+
+        let mut end = Vec::from(begin.as_bytes());
+        end.push(0xFF);
+
+        self.store.scan_raw(begin.as_bytes(), &end)
+            .map_err(StoreError::Backend)?
+            .into_iter()
+            .map(|(key, value)| {
+                let key = &key[self.prefix.encoded_len()..];
+                let key = match self.key_schema {
+                    Some(ref schema) => schema.decode(key)?,
+                    None => descending::decode(key)?,
+                };
+                Ok((K::from_segments(key)?, V::from_segments(descending::decode(&value)?)?))
+            })
+            .collect()
+    }
+
+    /// Read every entry in the map, in key order
+    pub fn entries(&self) -> Result<Vec<(K, V)>, StoreError<S::Error>> {
+        self.scan_prefix(&())
+    }
+
+    fn key(&self, key: &K) -> Tuple {
+        let mut tuple = self.prefix.clone();
+        key.pack_into(&mut tuple);
+        tuple
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::String;
+    use errors::TupleError;
+    use segment::SegmentType;
+    use Descending;
+
+    fn posts() -> TupleMap<(String, i64), (String, bool)> {
+        let mut map = TupleMap::in_memory();
+        map.insert(&(String::from("initech"), 1), &(String::from("tps"), false)).unwrap();
+        map.insert(&(String::from("acme"), 2), &(String::from("world"), true)).unwrap();
+        map.insert(&(String::from("acme"), 1), &(String::from("hello"), true)).unwrap();
+        map
+    }
+
+    #[test]
+    fn test_insert_get_remove() {
+        let mut map = posts();
+        let key = (String::from("acme"), 1);
+
+        assert_eq!(map.get(&key), Ok(Some((String::from("hello"), true))));
+        map.insert(&key, &(String::from("updated"), false)).unwrap();
+        assert_eq!(map.get(&key), Ok(Some((String::from("updated"), false))));
+
+        assert_eq!(map.remove(&key), Ok(Some((String::from("updated"), false))));
+        assert_eq!(map.remove(&key), Ok(None));
+        assert_eq!(map.get(&key), Ok(None));
+    }
+
+    #[test]
+    fn test_scan_prefix() {
+        let map = posts();
+
+        let keys = map.scan_prefix(&("acme",)).unwrap().into_iter().map(|(key, _)| key).collect::<Vec<_>>();
+        assert_eq!(keys, vec![(String::from("acme"), 1), (String::from("acme"), 2)]);
+
+        assert_eq!(map.scan_prefix(&("acme", 2i64)).unwrap().len(), 1);
+        assert!(map.scan_prefix(&("acm",)).unwrap().is_empty());
+        assert_eq!(map.entries().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_shared_store() {
+        let mut users = TupleMap::<(i64,), (String,)>::with_prefix(MemoryStore::new(), ::tuple!("users"));
+        users.insert(&(1,), &(String::from("alice"),)).unwrap();
+
+        let mut store = users.into_store();
+        store.set(&::tuple!("posts", 1), b"").unwrap();
+
+        let users = TupleMap::<(i64,), (String,)>::with_prefix(store, ::tuple!("users"));
+        assert_eq!(users.entries(), Ok(vec![((1,), (String::from("alice"),))]));
+        assert_eq!(users.store().len(), 2);
+    }
+
+    #[test]
+    fn test_descending_keys() {
+        let mut map = TupleMap::<(String, Descending<i64>), (String,)>::with_prefix(MemoryStore::new(), ::tuple!("posts"));
+        map.insert(&(String::from("acme"), Descending(1)), &(String::from("older"),)).unwrap();
+        map.insert(&(String::from("acme"), Descending(2)), &(String::from("newer"),)).unwrap();
+
+        let entries = map.entries().unwrap();
+        assert_eq!(entries[0].0, (String::from("acme"), Descending(2)));
+
+        let map = map.with_key_schema(TupleSchema::new().element(SegmentType::String).element(Descending(SegmentType::Integer)));
+        assert_eq!(map.scan_prefix(&("acme",)), Ok(vec![
+            ((String::from("acme"), Descending(2)), (String::from("newer"),)),
+            ((String::from("acme"), Descending(1)), (String::from("older"),)),
+        ]));
+        assert_eq!(map.get(&(String::from("acme"), Descending(1))), Ok(Some((String::from("older"),))));
+    }
+
+    #[test]
+    fn test_remove_invalid_value() {
+        let mut store = MemoryStore::new();
+        store.set(&::tuple!("acme", 1), ::tuple!("hello", 1).as_bytes()).unwrap();
+
+        let mut map = TupleMap::<(String, i64), (String, bool)>::new(store);
+        let key = (String::from("acme"), 1);
+
+        assert_eq!(map.remove(&key), Err(StoreError::Tuple(TupleError::TypeMismatch {
+            expected: SegmentType::Boolean,
+            found: SegmentType::Integer,
+        })));
+        assert_eq!(map.get(&key), Ok(None));
+        assert!(map.store().is_empty());
+    }
+
+    #[test]
+    fn test_type_mismatch() {
+        let mut store = MemoryStore::new();
+        store.set(&::tuple!("acme", "one"), ("hello", true).pack().as_bytes()).unwrap();
+
+        let map = TupleMap::<(String, i64), (String, bool)>::new(store);
+        assert_eq!(map.entries(), Err(StoreError::Tuple(TupleError::TypeMismatch {
+            expected: SegmentType::Integer,
+            found: SegmentType::String,
+        })));
+    }
+}
@@ -0,0 +1,78 @@
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::convert::Infallible;
+use core::ops::Bound;
+use super::{KeyValueStore, RawEntries};
+
+/// A store which keeps its entries in an in memory `BTreeMap`
+#[derive(Clone, Debug, Default)]
+pub struct MemoryStore {
+    entries: BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+impl MemoryStore {
+    /// Create a new, empty store
+    pub fn new() -> MemoryStore {
+        MemoryStore::default()
+    }
+
+    /// The number of entries in the store
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the store has no entries
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl KeyValueStore for MemoryStore {
+    type Error = Infallible;
+
+    fn get_raw(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Infallible> {
+        Ok(self.entries.get(key).cloned())
+    }
+
+    fn set_raw(&mut self, key: &[u8], value: &[u8]) -> Result<(), Infallible> {
+        self.entries.insert(Vec::from(key), Vec::from(value));
+        Ok(())
+    }
+
+    fn clear_raw(&mut self, key: &[u8]) -> Result<(), Infallible> {
+        self.entries.remove(key);
+        Ok(())
+    }
+
+    fn scan_raw(&self, begin: &[u8], end: &[u8]) -> Result<RawEntries, Infallible> {
+        if begin >= end {
+            return Ok(Vec::new());
+        }
+
+        Ok(self.entries.range::<[u8], _>((Bound::Included(begin), Bound::Excluded(end)))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use store::tests::check_store;
+
+    #[test]
+    fn test_memory_store() {
+        check_store(MemoryStore::new());
+    }
+
+    #[test]
+    fn test_scan_raw() {
+        let mut store = MemoryStore::new();
+        store.set_raw(&[1], &[1]).unwrap();
+        store.set_raw(&[2], &[2]).unwrap();
+
+        assert_eq!(store.scan_raw(&[1], &[2]), Ok(vec![(vec![1], vec![1])]));
+        assert_eq!(store.scan_raw(&[2], &[1]), Ok(vec![]));
+        assert_eq!(store.len(), 2);
+    }
+}
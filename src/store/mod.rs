@@ -0,0 +1,280 @@
+//! Ordered key value stores keyed by tuples
+//!
+//! `KeyValueStore` gives the same tuple keyed interface over any store which keeps its keys in
+//! byte order. Backends only implement the raw byte operations - encoding keys and decoding the
+//! keys returned by scans is shared.
+//!
+//! An in memory store is always available, and the `sled` and `lmdb` features add adapters for
+//! those embedded stores. `TupleMap` layers typed keys and values on top of any of them.
+
+use alloc::vec::Vec;
+use core::fmt;
+use descending;
+use errors::TupleError;
+use schema::TupleSchema;
+use segment::Segment;
+#[cfg(feature = "std")]
+use std::error::Error;
+use Tuple;
+
+mod memory;
+mod map;
+#[cfg(feature = "sled")]
+pub mod sled;
+#[cfg(feature = "lmdb")]
+pub mod lmdb;
+
+pub use self::memory::MemoryStore;
+pub use self::map::TupleMap;
+
+/// The raw entries returned by a scan, in key order
+pub type RawEntries = Vec<(Vec<u8>, Vec<u8>)>;
+
+/// The entries returned by a scan, with their keys decoded
+pub type Entries = Vec<(Vec<Segment>, Vec<u8>)>;
+
+/// A raw write made as part of a batch (see `KeyValueStore::write_batch`)
+#[derive(Clone, Debug, PartialEq)]
+pub enum BatchOp {
+    /// Store a value under an encoded key
+    Set(Vec<u8>, Vec<u8>),
+    /// Remove the value stored under an encoded key
+    Clear(Vec<u8>),
+}
+
+/// An ordered key value store
+///
+/// # Examples
+/// ```
+/// #[macro_use] extern crate binary_tuples;
+///
+/// use binary_tuples::segment::Segment;
+/// use binary_tuples::store::{KeyValueStore, MemoryStore};
+///
+/// # fn main() {
+/// let mut store = MemoryStore::new();
+/// store.set(&tuple!("users", 1), b"alice").unwrap();
+/// store.set(&tuple!("users", 2), b"bob").unwrap();
+/// store.set(&tuple!("posts", 1), b"hello").unwrap();
+///
+/// assert_eq!(store.get(&tuple!("users", 2)).unwrap(), Some(b"bob".to_vec()));
+///
+/// let users = store.scan_prefix(&tuple!("users")).unwrap();
+/// assert_eq!(users[0].0[1], Segment::Integer(1));
+/// assert_eq!(users.len(), 2);
+/// # }
+/// ```
+pub trait KeyValueStore {
+    /// The errors returned by the backing store
+    type Error;
+
+    /// Read the value stored under an encoded key
+    fn get_raw(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error>;
+
+    /// Store a value under an encoded key, replacing any existing value
+    fn set_raw(&mut self, key: &[u8], value: &[u8]) -> Result<(), Self::Error>;
+
+    /// Remove the value stored under an encoded key, if there is one
+    fn clear_raw(&mut self, key: &[u8]) -> Result<(), Self::Error>;
+
+    /// Read the entries with keys from `begin` (inclusive) to `end` (exclusive), in key order
+    ///
+    /// Callers guarantee that `begin` is less than `end`.
+    fn scan_raw(&self, begin: &[u8], end: &[u8]) -> Result<RawEntries, Self::Error>;
+
+    /// Make several raw writes, in order
+    ///
+    /// By default the writes are made one at a time, so a failure part way through leaves the
+    /// earlier writes in place. The sled and LMDB stores override this to make them atomically.
+    fn write_batch(&mut self, batch: &[BatchOp]) -> Result<(), Self::Error> {
+        for op in batch.iter() {
+            match op {
+                BatchOp::Set(key, value) => self.set_raw(key, value)?,
+                BatchOp::Clear(key) => self.clear_raw(key)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Read the value stored under a key
+    fn get(&self, key: &Tuple) -> Result<Option<Vec<u8>>, StoreError<Self::Error>> {
+        self.get_raw(key.as_bytes()).map_err(StoreError::Backend)
+    }
+
+    /// Store a value under a key, replacing any existing value
+    fn set(&mut self, key: &Tuple, value: &[u8]) -> Result<(), StoreError<Self::Error>> {
+        self.set_raw(key.as_bytes(), value).map_err(StoreError::Backend)
+    }
+
+    /// Remove the value stored under a key, if there is one
+    fn clear(&mut self, key: &Tuple) -> Result<(), StoreError<Self::Error>> {
+        self.clear_raw(key.as_bytes()).map_err(StoreError::Backend)
+    }
+
+    /// Read the entries with keys from `begin` (inclusive) to `end` (exclusive), decoding the keys
+    ///
+    /// Descending elements are picked out by their inverted type codes. Fails with
+    /// `StoreError::Tuple` if any of the keys in the range isn't a valid tuple.
+    fn scan(&self, begin: &Tuple, end: &Tuple) -> Result<Entries, StoreError<Self::Error>> {
+        scan_between(self, begin.as_bytes(), end.as_bytes(), descending::decode)
+    }
+
+    /// Read the entries with keys that contain `prefix`, decoding the keys (see `Tuple::range`)
+    ///
+    /// Fails as `scan` does - a single invalid key fails the whole scan.
+    fn scan_prefix(&self, prefix: &Tuple) -> Result<Entries, StoreError<Self::Error>> {
+        let (begin, end) = prefix.range();
+        scan_between(self, &begin, &end, descending::decode)
+    }
+
+    /// Read the entries with keys from `begin` (inclusive) to `end` (exclusive), decoding the keys
+    /// with a schema
+    ///
+    /// Fails with `StoreError::Tuple` if any of the keys in the range doesn't match the schema.
+    fn scan_with(&self, begin: &Tuple, end: &Tuple, schema: &TupleSchema) -> Result<Entries, StoreError<Self::Error>> {
+        scan_between(self, begin.as_bytes(), end.as_bytes(), |key| schema.decode(key))
+    }
+
+    /// Read the entries with keys that contain `prefix`, decoding the keys with a schema
+    fn scan_prefix_with(&self, prefix: &Tuple, schema: &TupleSchema) -> Result<Entries, StoreError<Self::Error>> {
+        let (begin, end) = prefix.range();
+        scan_between(self, &begin, &end, |key| schema.decode(key))
+    }
+}
+
+fn scan_between<S, F>(store: &S, begin: &[u8], end: &[u8], decode: F) -> Result<Entries, StoreError<S::Error>>
+    where S: KeyValueStore + ?Sized, F: Fn(&[u8]) -> Result<Vec<Segment>, TupleError> {
+    if begin >= end {
+        return Ok(Vec::new());
+    }
+
+    store.scan_raw(begin, end)
+        .map_err(StoreError::Backend)?
+        .into_iter()
+        .map(|(key, value)| Ok((decode(&key)?, value)))
+        .collect()
+}
+
+/// An error from a `KeyValueStore`
+#[derive(Debug, PartialEq)]
+pub enum StoreError<E> {
+    /// The backing store failed
+    Backend(E),
+    /// A key read from the store isn't a valid tuple
+    Tuple(TupleError),
+}
+
+impl<E: fmt::Display> fmt::Display for StoreError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StoreError::Backend(err) => write!(f, "store error: {}", err),
+            StoreError::Tuple(err) => write!(f, "invalid key in store: {}", err),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: Error + 'static> Error for StoreError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            StoreError::Backend(err) => Some(err),
+            StoreError::Tuple(err) => Some(err),
+        }
+    }
+}
+
+impl<E> From<TupleError> for StoreError<E> {
+    fn from(err: TupleError) -> Self {
+        StoreError::Tuple(err)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use alloc::boxed::Box;
+    use alloc::string::{String, ToString};
+    use core::fmt::Debug;
+    use schema::ElementSchema;
+    use segment::SegmentType;
+    use Descending;
+
+    /// Exercise the tuple level operations of a store, which should start out empty
+    pub(crate) fn check_store<S: KeyValueStore>(mut store: S) where S::Error: Debug {
+        store.set(&::tuple!("users", 2), b"bob").unwrap();
+        store.set(&::tuple!("users", 1), b"alice").unwrap();
+        store.set(&::tuple!("users", 1, "posts"), b"1").unwrap();
+        store.set(&::tuple!("posts", 1), b"hello").unwrap();
+
+        assert_eq!(store.get(&::tuple!("users", 1)).unwrap(), Some(b"alice".to_vec()));
+        assert_eq!(store.get(&::tuple!("users", 3)).unwrap(), None);
+
+        store.set(&::tuple!("users", 2), b"carol").unwrap();
+        assert_eq!(store.get(&::tuple!("users", 2)).unwrap(), Some(b"carol".to_vec()));
+
+        let users = store.scan_prefix(&::tuple!("users")).unwrap();
+        assert_eq!(users, vec![
+            (vec![Segment::String(String::from("users")), Segment::Integer(1)], b"alice".to_vec()),
+            (vec![Segment::String(String::from("users")), Segment::Integer(1), Segment::String(String::from("posts"))], b"1".to_vec()),
+            (vec![Segment::String(String::from("users")), Segment::Integer(2)], b"carol".to_vec()),
+        ]);
+
+        let range = store.scan(&::tuple!("users", 1), &::tuple!("users", 2)).unwrap();
+        assert_eq!(range.len(), 2);
+        assert!(store.scan(&::tuple!("users", 2), &::tuple!("users", 1)).unwrap().is_empty());
+
+        store.clear(&::tuple!("users", 1)).unwrap();
+        store.clear(&::tuple!("users", 3)).unwrap();
+        assert_eq!(store.get(&::tuple!("users", 1)).unwrap(), None);
+        assert_eq!(store.scan_prefix(&::tuple!("users")).unwrap().len(), 2);
+
+        store.write_batch(&[
+            BatchOp::Set(::tuple!("batch", 1).into_bytes(), b"one".to_vec()),
+            BatchOp::Set(::tuple!("batch", 2).into_bytes(), b"two".to_vec()),
+            BatchOp::Clear(::tuple!("batch", 1).into_bytes()),
+        ]).unwrap();
+        assert_eq!(store.get(&::tuple!("batch", 1)).unwrap(), None);
+        assert_eq!(store.get(&::tuple!("batch", 2)).unwrap(), Some(b"two".to_vec()));
+
+        store.set_raw(&[0x99], b"invalid").unwrap();
+        assert!(matches!(
+            store.scan(&Tuple::new(), &Tuple::from_bytes(&[0xFF])),
+            Err(StoreError::Tuple(TupleError::DecodeError { position: 0, type_code: 0x99 }))
+        ));
+    }
+
+    #[test]
+    fn test_scan_with_schema() {
+        let mut store = MemoryStore::new();
+        store.set(&::tuple!("posts", Descending(5), Descending("ab")), b"newer").unwrap();
+        store.set(&::tuple!("posts", Descending(1), Descending("ab")), b"older").unwrap();
+
+        let schema = TupleSchema::new()
+            .element(SegmentType::String)
+            .rest(Descending(ElementSchema::Any));
+        let posts = store.scan_prefix_with(&::tuple!("posts"), &schema).unwrap();
+
+        assert_eq!(posts[0].0, vec![
+            Segment::String(String::from("posts")),
+            Segment::Descending(Box::new(Segment::Integer(5))),
+            Segment::Descending(Box::new(Segment::String(String::from("ab")))),
+        ]);
+        assert_eq!(posts[1].1, b"older".to_vec());
+        assert_eq!(store.scan_with(&::tuple!("posts"), &::tuple!("posts", Descending(1)), &schema).unwrap().len(), 1);
+
+        assert_eq!(store.scan_prefix(&::tuple!("posts")).unwrap(), posts);
+        assert!(matches!(
+            store.scan_prefix_with(&::tuple!("posts"), &TupleSchema::new().element(SegmentType::String).rest(SegmentType::Integer)),
+            Err(StoreError::Tuple(_))
+        ));
+    }
+
+    #[test]
+    fn test_display() {
+        let err: StoreError<TupleError> = StoreError::Tuple(TupleError::TruncatedTuple);
+        assert_eq!(err.to_string(), "invalid key in store: tuple ended before the end of the input");
+
+        let err = StoreError::Backend(TupleError::TruncatedTuple);
+        assert_eq!(err.to_string(), "store error: tuple ended before the end of the input");
+    }
+}
@@ -0,0 +1,87 @@
+//! A `KeyValueStore` backed by a sled tree
+
+use alloc::vec::Vec;
+use std::path::Path;
+use sled::{Batch, Error, Tree};
+use super::{BatchOp, KeyValueStore, RawEntries};
+
+/// A store which keeps its entries in a sled `Tree`
+///
+/// Writes go straight to the tree, and are made durable as sled flushes it. Batches are applied
+/// atomically.
+#[derive(Clone, Debug)]
+pub struct SledStore {
+    tree: Tree,
+}
+
+impl SledStore {
+    /// Wrap a tree, which may be the default tree of a `sled::Db`
+    pub fn new(tree: Tree) -> SledStore {
+        SledStore { tree }
+    }
+
+    /// Open the database at `path`, storing entries in its default tree
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<SledStore, Error> {
+        let db = ::sled::open(path)?;
+        Ok(SledStore::new(Tree::clone(&db)))
+    }
+
+    /// The tree which holds the entries
+    pub fn tree(&self) -> &Tree {
+        &self.tree
+    }
+}
+
+impl From<Tree> for SledStore {
+    fn from(tree: Tree) -> SledStore {
+        SledStore::new(tree)
+    }
+}
+
+impl KeyValueStore for SledStore {
+    type Error = Error;
+
+    fn get_raw(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+        Ok(self.tree.get(key)?.map(|value| value.to_vec()))
+    }
+
+    fn set_raw(&mut self, key: &[u8], value: &[u8]) -> Result<(), Error> {
+        self.tree.insert(key, value)?;
+        Ok(())
+    }
+
+    fn clear_raw(&mut self, key: &[u8]) -> Result<(), Error> {
+        self.tree.remove(key)?;
+        Ok(())
+    }
+
+    fn scan_raw(&self, begin: &[u8], end: &[u8]) -> Result<RawEntries, Error> {
+        self.tree.range(begin..end)
+            .map(|entry| entry.map(|(key, value)| (key.to_vec(), value.to_vec())))
+            .collect()
+    }
+
+    fn write_batch(&mut self, batch: &[BatchOp]) -> Result<(), Error> {
+        let mut writes = Batch::default();
+        for op in batch.iter() {
+            match op {
+                BatchOp::Set(key, value) => writes.insert(&key[..], &value[..]),
+                BatchOp::Clear(key) => writes.remove(&key[..]),
+            }
+        }
+        self.tree.apply_batch(writes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use store::tests::check_store;
+
+    #[test]
+    fn test_sled_store() {
+        let db = ::sled::Config::new().temporary(true).open().unwrap();
+
+        check_store(SledStore::new(Tree::clone(&db)));
+    }
+}
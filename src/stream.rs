@@ -0,0 +1,381 @@
+//! Streaming tuples to and from `std::io` readers and writers
+//!
+//! A stream contains a sequence of tuples, each followed by a single `0x00` byte - the same
+//! terminator used for nested tuples. As `0x00` bytes within strings are always escaped and every
+//! other element has a known length, the end of each tuple can be found without any extra framing.
+//!
+//! Descending elements can't be streamed, as their inverted bytes may contain unescaped `0x00`
+//! bytes - `TupleWriter` rejects them before writing anything.
+
+use alloc::vec::Vec;
+use std::io;
+use std::io::{BufRead, Write};
+use memchr::memchr;
+use constants::*;
+use decode::{element_end, DecodeOptions};
+use errors::TupleError;
+use segment::Segment;
+use utils::fixed_len;
+use Tuple;
+
+/// Writes tuples to an `io::Write` as they are built
+///
+/// # Examples
+/// ```
+/// #[macro_use] extern crate binary_tuples;
+///
+/// use binary_tuples::{TupleWriter, segment::Segment};
+///
+/// # fn main() {
+/// let mut writer = TupleWriter::new(Vec::new());
+///
+/// writer.write_segment(&Segment::Const("users")).unwrap();
+/// writer.write_segment(&Segment::Integer(1)).unwrap();
+/// writer.end_tuple().unwrap();
+///
+/// writer.write_tuple(&tuple!("users", 2)).unwrap();
+///
+/// assert_eq!(writer.into_inner(), vec![
+///     2, 117, 115, 101, 114, 115, 0, 21, 1, 0,
+///     2, 117, 115, 101, 114, 115, 0, 21, 2, 0,
+/// ]);
+/// # }
+/// ```
+pub struct TupleWriter<W: Write> {
+    writer: W,
+    scratch: Vec<u8>,
+}
+
+impl<W: Write> TupleWriter<W> {
+    /// Create a new tuple writer
+    ///
+    /// Segments are written to the underlying writer individually, so wrapping it in a
+    /// `BufWriter` is recommended.
+    pub fn new(writer: W) -> TupleWriter<W> {
+        TupleWriter {
+            writer,
+            scratch: Vec::with_capacity(64),
+        }
+    }
+
+    /// Append a segment to the tuple currently being written
+    ///
+    /// Fails with `io::ErrorKind::InvalidInput` if the segment is a descending element (or raw
+    /// bytes which aren't valid elements), as it couldn't be read back.
+    pub fn write_segment(&mut self, segment: &Segment) -> io::Result<()> {
+        self.scratch.clear();
+        segment.encode(&mut self.scratch);
+        check_framed(&self.scratch)?;
+
+        self.writer.write_all(&self.scratch)
+    }
+
+    /// Append the contents of an existing tuple to the tuple currently being written
+    ///
+    /// Fails with `io::ErrorKind::InvalidInput` if the tuple has descending elements or isn't
+    /// valid, as it couldn't be read back.
+    pub fn write_builder(&mut self, tuple: &Tuple) -> io::Result<()> {
+        check_framed(tuple.as_bytes())?;
+        self.writer.write_all(tuple.as_bytes())
+    }
+
+    /// Finish the tuple currently being written
+    pub fn end_tuple(&mut self) -> io::Result<()> {
+        self.writer.write_all(&[NULL])
+    }
+
+    /// Write a complete tuple
+    pub fn write_tuple(&mut self, tuple: &Tuple) -> io::Result<()> {
+        self.write_builder(tuple)?;
+        self.end_tuple()
+    }
+
+    /// Flush the underlying writer
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Return the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Check that the end of every element can be found, which fails for descending elements as their
+/// inverted type codes are never valid
+fn check_framed(input: &[u8]) -> io::Result<()> {
+    let mut index = 0;
+    while index < input.len() {
+        index = element_end(input, index, usize::MAX)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "descending or invalid elements can't be streamed"))?;
+    }
+
+    Ok(())
+}
+
+/// Reads the tuples written by a `TupleWriter` from an `io::BufRead`
+///
+/// Iterating over the decoder yields the decoded segments of each tuple, while `read_tuple` can be
+/// used to read the encoded tuples without decoding them.
+///
+/// # Examples
+/// ```
+/// use binary_tuples::{TupleDecoder, segment::Segment};
+///
+/// let input: &[u8] = &[2, 117, 115, 101, 114, 115, 0, 21, 1, 0, 21, 2, 0];
+///
+/// let tuples = TupleDecoder::new(input)
+///     .collect::<Result<Vec<_>, _>>()
+///     .unwrap();
+///
+/// assert_eq!(tuples, vec![
+///     vec![Segment::String(String::from("users")), Segment::Integer(1)],
+///     vec![Segment::Integer(2)],
+/// ]);
+/// ```
+pub struct TupleDecoder<R: BufRead> {
+    reader: R,
+    options: DecodeOptions,
+}
+
+impl<R: BufRead> TupleDecoder<R> {
+    /// Create a new tuple decoder using the default `DecodeOptions`
+    pub fn new(reader: R) -> TupleDecoder<R> {
+        TupleDecoder::with_options(reader, DecodeOptions::default())
+    }
+
+    /// Create a new tuple decoder which applies the given limits to each tuple
+    ///
+    /// The size limit is checked as the tuple is read, so a stream of untrusted input can't cause
+    /// an unbounded amount of memory to be buffered.
+    pub fn with_options(reader: R, options: DecodeOptions) -> TupleDecoder<R> {
+        TupleDecoder { reader, options }
+    }
+
+    /// Read the next encoded tuple, returning `None` at the end of the stream
+    ///
+    /// Fails with `io::ErrorKind::UnexpectedEof` if the stream ends part way through a tuple and
+    /// with `io::ErrorKind::InvalidData` if an unknown type code is found or a limit is exceeded.
+    pub fn read_tuple(&mut self) -> io::Result<Option<Tuple>> {
+        let mut buffer = Vec::new();
+        let mut depth = 0;
+        let mut elements = 0;
+
+        loop {
+            self.options.check_bytes(buffer.len())?;
+
+            let code = match self.read_byte()? {
+                Some(code) => code,
+                None if buffer.is_empty() => return Ok(None),
+                None => return Err(io::ErrorKind::UnexpectedEof.into()),
+            };
+
+            if code != NULL {
+                elements += 1;
+                self.options.check_elements(elements, buffer.len())?;
+            }
+
+            let length = match code {
+                NULL if depth == 0 => return Ok(Some(Tuple { buffer })),
+                NULL => {
+                    depth -= 1;
+                    0
+                }
+                NESTED_CODE => {
+                    depth += 1;
+                    self.options.check_depth(depth, buffer.len())?;
+                    0
+                }
+                BYTES_CODE | STRING_CODE => {
+                    buffer.push(code);
+                    self.read_byte_string(&mut buffer)?;
+                    continue;
+                }
+                type_code => match fixed_len(type_code) {
+                    Some(length) => length,
+                    None => {
+                        let position = buffer.len();
+                        return Err(TupleError::DecodeError { position, type_code }.into());
+                    }
+                }
+            };
+
+            buffer.push(code);
+
+            let start = buffer.len();
+            buffer.resize(start + length, 0);
+            self.reader.read_exact(&mut buffer[start..])?;
+        }
+    }
+
+    /// Return the underlying reader
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        let value = self.reader.fill_buf()?.first().cloned();
+        if value.is_some() {
+            self.reader.consume(1);
+        }
+
+        Ok(value)
+    }
+
+    /// Copy an escaped byte string up to and including its terminator, which may be split
+    /// across several reads.
+    fn read_byte_string(&mut self, buffer: &mut Vec<u8>) -> io::Result<()> {
+        loop {
+            self.options.check_bytes(buffer.len())?;
+
+            let (found, used) = {
+                let available = self.reader.fill_buf()?;
+                if available.is_empty() {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+
+                match memchr(NULL, available) {
+                    Some(index) => {
+                        buffer.extend_from_slice(&available[..=index]);
+                        (true, index + 1)
+                    }
+                    None => {
+                        buffer.extend_from_slice(available);
+                        (false, available.len())
+                    }
+                }
+            };
+            self.reader.consume(used);
+
+            if found {
+                match self.reader.fill_buf()?.first() {
+                    Some(&NULL_ESCAPE) => {
+                        buffer.push(NULL_ESCAPE);
+                        self.reader.consume(1);
+                    }
+                    _ => return Ok(())
+                }
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for TupleDecoder<R> {
+    type Item = io::Result<Vec<Segment>>;
+
+    fn next(&mut self) -> Option<io::Result<Vec<Segment>>> {
+        match self.read_tuple() {
+            Ok(Some(tuple)) => Some(tuple.as_segments_with(&self.options).map_err(io::Error::from)),
+            Ok(None) => None,
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::boxed::Box;
+    use alloc::string::String;
+    use std::io::BufReader;
+
+    fn encode(tuples: &[Vec<Segment>]) -> Vec<u8> {
+        let mut writer = TupleWriter::new(Vec::new());
+        for tuple in tuples.iter() {
+            for segment in tuple.iter() {
+                writer.write_segment(segment).unwrap();
+            }
+            writer.end_tuple().unwrap();
+        }
+
+        writer.into_inner()
+    }
+
+    #[test]
+    fn test_write_tuples() {
+        let bytes = encode(&[vec![Segment::Const("a")], vec![], vec![Segment::Integer(1)]]);
+
+        assert_eq!(bytes, vec![STRING_CODE, 97, NULL, NULL, NULL, INT_ZERO_CODE + 1, 1, NULL]);
+    }
+
+    #[test]
+    fn test_round_trip_across_buffer_boundaries() {
+        let tuples = vec![
+            vec![Segment::String(String::from("a\0b")), Segment::Bytes(vec![0, 0, 255, 0])],
+            vec![Segment::Nested(vec![Segment::Bytes(vec![0]), Segment::Nested(vec![])]), Segment::Double(1.5)],
+            vec![],
+            vec![Segment::Integer(-5000), Segment::Boolean(true), Segment::Float(2.0)],
+        ];
+        let bytes = encode(&tuples);
+
+        for capacity in 1..bytes.len() {
+            let decoder = TupleDecoder::new(BufReader::with_capacity(capacity, &bytes[..]));
+            let result = decoder.collect::<io::Result<Vec<_>>>().unwrap();
+
+            assert_eq!(result, tuples);
+        }
+    }
+
+    #[test]
+    fn test_read_tuple() {
+        let bytes: &[u8] = &[INT_ZERO_CODE, NULL, TRUE_CODE, NULL];
+        let mut decoder = TupleDecoder::new(bytes);
+
+        assert_eq!(decoder.read_tuple().unwrap().unwrap().as_bytes(), &[INT_ZERO_CODE]);
+        assert_eq!(decoder.read_tuple().unwrap().unwrap().as_bytes(), &[TRUE_CODE]);
+        assert!(decoder.read_tuple().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_unexpected_eof() {
+        for bytes in [&[STRING_CODE, 97][..], &[INT_ZERO_CODE + 2, 1][..], &[INT_ZERO_CODE][..], &[NESTED_CODE, NULL][..]].iter() {
+            let err = TupleDecoder::new(*bytes).read_tuple().err().unwrap();
+
+            assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        }
+    }
+
+    #[test]
+    fn test_limits() {
+        let bytes: &[u8] = &[NESTED_CODE, NESTED_CODE, INT_ZERO_CODE, NULL, NULL, NULL];
+        let err = TupleDecoder::with_options(bytes, DecodeOptions::new().max_depth(1)).read_tuple().err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let bytes: &[u8] = &[INT_ZERO_CODE, TRUE_CODE, FALSE_CODE, NULL];
+        let err = TupleDecoder::with_options(bytes, DecodeOptions::new().max_elements(2)).read_tuple().err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let bytes = encode(&[vec![Segment::Bytes(vec![1; 1000])]]);
+        for capacity in [1, 8, 4096].iter() {
+            let mut decoder = TupleDecoder::with_options(BufReader::with_capacity(*capacity, &bytes[..]), DecodeOptions::new().max_bytes(100));
+            assert_eq!(decoder.read_tuple().err().unwrap().kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn test_reject_descending() {
+        let mut writer = TupleWriter::new(Vec::new());
+
+        let err = writer.write_tuple(&::tuple!("posts", ::Descending(5))).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        let err = writer.write_segment(&Segment::Descending(Box::new(Segment::Integer(5)))).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        let err = writer.write_builder(&Tuple::from_bytes(&[STRING_CODE, 97])).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        // Nothing was written, so the stream stays in sync
+        writer.write_tuple(&::tuple!("posts", 5)).unwrap();
+        let bytes = writer.into_inner();
+        assert_eq!(TupleDecoder::new(&bytes[..]).collect::<io::Result<Vec<_>>>().unwrap(), vec![
+            vec![Segment::String(String::from("posts")), Segment::Integer(5)],
+        ]);
+    }
+
+    #[test]
+    fn test_invalid_type_code() {
+        let bytes: &[u8] = &[INT_ZERO_CODE, 0x99, NULL];
+        let err = TupleDecoder::new(bytes).read_tuple().err().unwrap();
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,514 @@
+//! A human readable representation of tuples
+//!
+//! Tuples are written in a syntax similar to the one used by the FoundationDB tooling:
+//!
+//! * Strings are double quoted - `"users"`
+//! * Byte strings are prefixed with `b` - `b"\x01\x02"`
+//! * Integers and doubles are written as plain numbers - `42`, `-1`, `1.5`
+//! * Single precision floats are tagged - `f32(1.5)`
+//! * Booleans are written as `true` and `false`
+//! * UUIDs are tagged - `UUID("c5c2a280-e47c-4181-94b3-c23cd5faede8")`
+//! * Nested tuples are surrounded by parentheses - `("nested", 1)`
+//! * Descending elements are tagged - `desc("users")`, and can't appear inside nested tuples
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::fmt::Write;
+use decode::{Limit, DEFAULT_MAX_DEPTH};
+use errors::TupleError;
+use segment::Segment;
+
+/// Format a list of segments as a textual tuple
+///
+/// # Examples
+/// ```
+/// use binary_tuples::{text, segment::Segment};
+///
+/// let segments = vec![Segment::Const("users"), Segment::Integer(1)];
+///
+/// assert_eq!(text::format(&segments), "(\"users\", 1)");
+/// ```
+pub fn format(segments: &[Segment]) -> String {
+    let mut output = String::new();
+    write_tuple(&mut output, segments).unwrap();
+    output
+}
+
+/// Parse a textual tuple into a list of segments
+///
+/// The surrounding parentheses of the outermost tuple are optional.
+/// Fails with `TupleError::LimitExceeded` if tuples are nested more than `DEFAULT_MAX_DEPTH`
+/// deep, the same limit applied when decoding.
+///
+/// # Examples
+/// ```
+/// use binary_tuples::{text, segment::Segment};
+///
+/// let segments = text::parse("(\"users\", 1, (true))").unwrap();
+///
+/// assert_eq!(segments, vec![
+///     Segment::String(String::from("users")),
+///     Segment::Integer(1),
+///     Segment::Nested(vec![Segment::Boolean(true)]),
+/// ]);
+/// ```
+pub fn parse(input: &str) -> Result<Vec<Segment>, TupleError> {
+    let mut parser = Parser { input: input.as_bytes(), position: 0, depth: 0 };
+
+    parser.skip_whitespace();
+    let segments = if parser.peek() == Some(b'(') {
+        parser.parse_tuple()?
+    } else {
+        parser.parse_elements(None)?
+    };
+    parser.skip_whitespace();
+
+    if parser.position != input.len() {
+        return Err(parser.error());
+    }
+
+    Ok(segments)
+}
+
+impl fmt::Display for Segment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_segment(f, self)
+    }
+}
+
+fn write_tuple<W: Write>(output: &mut W, segments: &[Segment]) -> fmt::Result {
+    output.write_char('(')?;
+    write_elements(output, segments)?;
+    output.write_char(')')
+}
+
+fn write_elements<W: Write>(output: &mut W, segments: &[Segment]) -> fmt::Result {
+    for (i, segment) in segments.iter().enumerate() {
+        if i > 0 {
+            output.write_str(", ")?;
+        }
+        write_segment(output, segment)?;
+    }
+
+    Ok(())
+}
+
+fn write_segment<W: Write>(output: &mut W, segment: &Segment) -> fmt::Result {
+    match segment {
+        Segment::Bytes(data) => {
+            output.write_str("b\"")?;
+            for byte in data.iter() {
+                match *byte {
+                    b'"' => output.write_str("\\\"")?,
+                    b'\\' => output.write_str("\\\\")?,
+                    32..=126 => output.write_char(*byte as char)?,
+                    value => write!(output, "\\x{:02x}", value)?,
+                }
+            }
+            output.write_char('"')
+        }
+        Segment::String(data) => write_string(output, data),
+        Segment::Const(data) => write_string(output, data),
+        Segment::Nested(inner) => write_tuple(output, inner),
+        Segment::Integer(value) => write!(output, "{}", value),
+        Segment::Float(value) => write!(output, "f32({:?})", value),
+        Segment::Double(value) => write!(output, "{:?}", value),
+        Segment::Boolean(value) => write!(output, "{}", value),
+        Segment::UUID(value) => {
+            output.write_str("UUID(\"")?;
+            write_uuid(output, value)?;
+            output.write_str("\")")
+        }
+        Segment::Tuple(value) => match Segment::decode(value) {
+            Ok(inner) => write_elements(output, &inner),
+            Err(_) => write!(output, "<invalid tuple of {} bytes>", value.len()),
+        },
+        Segment::Descending(inner) => {
+            output.write_str("desc(")?;
+            write_segment(output, inner)?;
+            output.write_char(')')
+        }
+    }
+}
+
+fn write_string<W: Write>(output: &mut W, data: &str) -> fmt::Result {
+    output.write_char('"')?;
+    for c in data.chars() {
+        match c {
+            '"' => output.write_str("\\\"")?,
+            '\\' => output.write_str("\\\\")?,
+            '\n' => output.write_str("\\n")?,
+            '\r' => output.write_str("\\r")?,
+            '\t' => output.write_str("\\t")?,
+            c if (c as u32) < 32 || c as u32 == 127 => write!(output, "\\x{:02x}", c as u32)?,
+            c => output.write_char(c)?,
+        }
+    }
+    output.write_char('"')
+}
+
+/// Write a UUID in its hyphenated form
+pub(crate) fn write_uuid<W: Write>(output: &mut W, value: &[u8; 16]) -> fmt::Result {
+    for (i, byte) in value.iter().enumerate() {
+        if i == 4 || i == 6 || i == 8 || i == 10 {
+            output.write_char('-')?;
+        }
+        write!(output, "{:02x}", byte)?;
+    }
+
+    Ok(())
+}
+
+/// Parse a UUID in either its hyphenated or its simple (32 hex digits) form
+pub(crate) fn parse_uuid(input: &str) -> Option<[u8; 16]> {
+    let input = input.as_bytes();
+    let digits = match input.len() {
+        32 => input.to_vec(),
+        36 if [8, 13, 18, 23].iter().all(|i| input[*i] == b'-') => {
+            input.iter().cloned().filter(|c| *c != b'-').collect()
+        }
+        _ => return None
+    };
+
+    let mut value = [0; 16];
+    for (byte, pair) in value.iter_mut().zip(digits.chunks(2)) {
+        let high = (pair[0] as char).to_digit(16)?;
+        let low = (pair[1] as char).to_digit(16)?;
+        *byte = (high << 4 | low) as u8;
+    }
+
+    Some(value)
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    position: usize,
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self) -> TupleError {
+        TupleError::ParseError { position: self.position }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.position).cloned()
+    }
+
+    fn next(&mut self) -> Result<u8, TupleError> {
+        match self.peek() {
+            Some(value) => {
+                self.position += 1;
+                Ok(value)
+            }
+            None => Err(self.error())
+        }
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<(), TupleError> {
+        if self.peek() == Some(expected) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(self.error())
+        }
+    }
+
+    /// Start parsing a nested value, failing if it is nested deeper than `DEFAULT_MAX_DEPTH`
+    fn enter(&mut self) -> Result<(), TupleError> {
+        if self.depth == DEFAULT_MAX_DEPTH {
+            return Err(TupleError::LimitExceeded { limit: Limit::Depth, position: self.position });
+        }
+        self.depth += 1;
+
+        Ok(())
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ') | Some(b'\t') | Some(b'\n') | Some(b'\r') = self.peek() {
+            self.position += 1;
+        }
+    }
+
+    fn parse_tuple(&mut self) -> Result<Vec<Segment>, TupleError> {
+        self.expect(b'(')?;
+        let segments = self.parse_elements(Some(b')'))?;
+        self.expect(b')')?;
+
+        Ok(segments)
+    }
+
+    fn parse_elements(&mut self, terminator: Option<u8>) -> Result<Vec<Segment>, TupleError> {
+        let mut segments = Vec::new();
+
+        loop {
+            self.skip_whitespace();
+            if self.peek() == terminator {
+                return Ok(segments);
+            }
+
+            segments.push(self.parse_segment()?);
+
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                value if value == terminator => return Ok(segments),
+                _ => return Err(self.error())
+            }
+        }
+    }
+
+    fn parse_segment(&mut self) -> Result<Segment, TupleError> {
+        match self.peek() {
+            Some(b'(') => {
+                let start = self.position;
+                self.enter()?;
+                let inner = self.parse_tuple()?;
+                self.depth -= 1;
+
+                if inner.iter().any(|segment| matches!(segment, Segment::Descending(_))) {
+                    return Err(TupleError::ParseError { position: start });
+                }
+
+                Ok(Segment::Nested(inner))
+            }
+            Some(b'"') => {
+                let bytes = self.parse_quoted()?;
+                String::from_utf8(bytes).map(Segment::String).map_err(|_| self.error())
+            }
+            Some(b'b') if self.input.get(self.position + 1) == Some(&b'"') => {
+                self.position += 1;
+                Ok(Segment::Bytes(self.parse_quoted()?))
+            }
+            Some(b'-') | Some(b'+') | Some(b'.') | Some(b'0'..=b'9') => self.parse_number(),
+            Some(b'a'..=b'z') | Some(b'A'..=b'Z') => self.parse_word(),
+            _ => Err(self.error())
+        }
+    }
+
+    fn parse_quoted(&mut self) -> Result<Vec<u8>, TupleError> {
+        self.expect(b'"')?;
+
+        let mut bytes = Vec::new();
+        loop {
+            match self.next()? {
+                b'"' => return Ok(bytes),
+                b'\\' => match self.next()? {
+                    b'\\' => bytes.push(b'\\'),
+                    b'"' => bytes.push(b'"'),
+                    b'n' => bytes.push(b'\n'),
+                    b'r' => bytes.push(b'\r'),
+                    b't' => bytes.push(b'\t'),
+                    b'0' => bytes.push(0),
+                    b'x' => {
+                        let high = self.parse_hex_digit()?;
+                        let low = self.parse_hex_digit()?;
+                        bytes.push(high << 4 | low);
+                    }
+                    _ => {
+                        self.position -= 1;
+                        return Err(self.error());
+                    }
+                },
+                value => bytes.push(value)
+            }
+        }
+    }
+
+    fn parse_hex_digit(&mut self) -> Result<u8, TupleError> {
+        let value = match self.peek() {
+            Some(value @ b'0'..=b'9') => value - b'0',
+            Some(value @ b'a'..=b'f') => value - b'a' + 10,
+            Some(value @ b'A'..=b'F') => value - b'A' + 10,
+            _ => return Err(self.error())
+        };
+        self.position += 1;
+
+        Ok(value)
+    }
+
+    fn take_while<F: Fn(u8) -> bool>(&mut self, predicate: F) -> &'a str {
+        let start = self.position;
+        while self.peek().is_some_and(&predicate) {
+            self.position += 1;
+        }
+
+        // Only ascii characters are accepted by the callers' predicates
+        core::str::from_utf8(&self.input[start..self.position]).unwrap()
+    }
+
+    fn parse_number(&mut self) -> Result<Segment, TupleError> {
+        let start = self.position;
+        let token = self.take_while(|c| c.is_ascii_alphanumeric() || c == b'-' || c == b'+' || c == b'.');
+
+        let is_integer = token.bytes().enumerate()
+            .all(|(i, c)| c.is_ascii_digit() || (i == 0 && (c == b'-' || c == b'+')));
+
+        let result = if is_integer {
+            token.parse::<i64>().map(Segment::Integer).ok()
+        } else {
+            token.parse::<f64>().map(Segment::Double).ok()
+        };
+
+        result.ok_or(TupleError::ParseError { position: start })
+    }
+
+    fn parse_word(&mut self) -> Result<Segment, TupleError> {
+        let start = self.position;
+        let word = self.take_while(|c| c.is_ascii_alphanumeric());
+
+        match word {
+            "true" => Ok(Segment::Boolean(true)),
+            "false" => Ok(Segment::Boolean(false)),
+            "NaN" | "inf" => Ok(Segment::Double(word.parse().unwrap())),
+            "f32" => {
+                self.expect(b'(')?;
+                self.skip_whitespace();
+                let value = match self.parse_number()? {
+                    Segment::Integer(value) => value as f32,
+                    Segment::Double(value) => value as f32,
+                    _ => unreachable!()
+                };
+                self.skip_whitespace();
+                self.expect(b')')?;
+
+                Ok(Segment::Float(value))
+            }
+            "UUID" => {
+                self.expect(b'(')?;
+                self.skip_whitespace();
+                let position = self.position;
+                let value = String::from_utf8(self.parse_quoted()?).map_err(|_| self.error())?;
+                let uuid = parse_uuid(&value)
+                    .ok_or(TupleError::ParseError { position })?;
+                self.skip_whitespace();
+                self.expect(b')')?;
+
+                Ok(Segment::UUID(uuid))
+            }
+            "desc" => {
+                self.expect(b'(')?;
+                self.enter()?;
+                self.skip_whitespace();
+                let inner = self.parse_segment()?;
+                self.skip_whitespace();
+                self.expect(b')')?;
+                self.depth -= 1;
+
+                Ok(Segment::Descending(Box::new(inner)))
+            }
+            _ => Err(TupleError::ParseError { position: start })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_types() {
+        let segments = vec![
+            Segment::Const("a\"b"),
+            Segment::Bytes(vec![0, 65, 255]),
+            Segment::Integer(-5),
+            Segment::Float(1.5),
+            Segment::Double(2.0),
+            Segment::Boolean(false),
+            Segment::Nested(vec![Segment::Integer(1)]),
+        ];
+
+        assert_eq!(format(&segments), "(\"a\\\"b\", b\"\\x00A\\xff\", -5, f32(1.5), 2.0, false, (1))");
+    }
+
+    #[test]
+    fn test_format_uuid() {
+        let uuid = [197, 194, 162, 128, 228, 124, 65, 129, 148, 179, 194, 60, 213, 250, 237, 232];
+
+        assert_eq!(format(&[Segment::UUID(uuid)]), "(UUID(\"c5c2a280-e47c-4181-94b3-c23cd5faede8\"))");
+    }
+
+    #[test]
+    fn test_parse_uuid() {
+        let uuid = [197, 194, 162, 128, 228, 124, 65, 129, 148, 179, 194, 60, 213, 250, 237, 232];
+
+        assert_eq!(parse_uuid("c5c2a280-e47c-4181-94b3-c23cd5faede8"), Some(uuid));
+        assert_eq!(parse_uuid("C5C2A280E47C418194B3C23CD5FAEDE8"), Some(uuid));
+        assert_eq!(parse_uuid("c5c2a280-e47c-4181-94b3-c23cd5faede"), None);
+        assert_eq!(parse_uuid("c5c2a280e-47c-4181-94b3-c23cd5faede8"), None);
+        assert_eq!(parse_uuid("g5c2a280-e47c-4181-94b3-c23cd5faede8"), None);
+    }
+
+    #[test]
+    fn test_format_embedded_tuple() {
+        let segments = vec![Segment::Tuple(vec![2, 97, 0, 21, 1]), Segment::Boolean(true)];
+
+        assert_eq!(format(&segments), "(\"a\", 1, true)");
+    }
+
+    #[test]
+    fn test_parse_round_trip() {
+        let input = "(\"a\\\"b\", b\"\\x00A\\xff\", -5, f32(1.5), 2.0, false, (1, ()), UUID(\"c5c2a280-e47c-4181-94b3-c23cd5faede8\"))";
+
+        assert_eq!(format(&parse(input).unwrap()), input);
+    }
+
+    #[test]
+    fn test_parse_descending() {
+        let segments = parse("(\"users\", desc( 30 ), desc(\"alice\"))").unwrap();
+
+        assert_eq!(segments[1], Segment::Descending(Box::new(Segment::Integer(30))));
+        assert_eq!(format(&segments), "(\"users\", desc(30), desc(\"alice\"))");
+        assert!(parse("desc(1, 2)").is_err());
+        assert_eq!(parse("(1, (2, desc(3)))"), Err(TupleError::ParseError { position: 4 }));
+        assert_eq!(parse("(desc((desc(3))))"), Err(TupleError::ParseError { position: 6 }));
+    }
+
+    #[test]
+    fn test_parse_without_parentheses() {
+        let result = parse(" \"users\" , 1 ").unwrap();
+
+        assert_eq!(result, vec![Segment::String(String::from("users")), Segment::Integer(1)]);
+    }
+
+    #[test]
+    fn test_parse_empty() {
+        assert_eq!(parse("()").unwrap(), vec![]);
+        assert_eq!(parse("").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_parse_numbers() {
+        let result = parse("(+1, -9223372036854775808, 1e3, -inf)").unwrap();
+
+        assert_eq!(result, vec![
+            Segment::Integer(1),
+            Segment::Integer(i64::MIN),
+            Segment::Double(1000.0),
+            Segment::Double(f64::NEG_INFINITY),
+        ]);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(parse("(1, 2"), Err(TupleError::ParseError { position: 5 }));
+        assert_eq!(parse("(1 2)"), Err(TupleError::ParseError { position: 3 }));
+        assert_eq!(parse("(nope)"), Err(TupleError::ParseError { position: 1 }));
+        assert_eq!(parse("(\"\\q\")"), Err(TupleError::ParseError { position: 3 }));
+    }
+
+    #[test]
+    fn test_parse_depth() {
+        let nested = "(".repeat(DEFAULT_MAX_DEPTH + 1) + &")".repeat(DEFAULT_MAX_DEPTH + 1);
+        assert!(parse(&nested).is_ok());
+
+        let error = Err(TupleError::LimitExceeded { limit: Limit::Depth, position: DEFAULT_MAX_DEPTH + 1 });
+        assert_eq!(parse(&"(".repeat(DEFAULT_MAX_DEPTH + 2)), error);
+        assert_eq!(parse(&"(".repeat(200_000)), error);
+
+        let error = Err(TupleError::LimitExceeded { limit: Limit::Depth, position: DEFAULT_MAX_DEPTH * 5 + 5 });
+        assert_eq!(parse(&"desc(".repeat(200_000)), error);
+    }
+}
@@ -0,0 +1,183 @@
+//! Random access into encoded tuples
+//!
+//! A `TupleIndex` records where each top level element of an encoded tuple starts, so individual
+//! elements (or runs of elements) can be read out of wide keys without decoding the rest.
+
+use alloc::vec::Vec;
+use core::ops::Range;
+use decode::DecodeOptions;
+use errors::TupleError;
+use segment::Segment;
+
+/// The offsets of the top level elements of an encoded tuple
+///
+/// Building the index walks over the tuple once, checking its structure but not decoding any of
+/// the elements.
+///
+/// # Examples
+/// ```
+/// #[macro_use] extern crate binary_tuples;
+///
+/// use binary_tuples::{TupleIndex, segment::Segment};
+///
+/// # fn main() {
+/// let tuple = tuple!("users", 1234, "posts", 98765);
+/// let index = TupleIndex::new(tuple.as_bytes()).unwrap();
+///
+/// assert_eq!(index.len(), 4);
+/// assert_eq!(index.get(1), Some(&[22, 4, 210][..]));
+/// assert_eq!(index.slice(2..4), Some(tuple!("posts", 98765).as_bytes()));
+/// assert_eq!(index.segment(3).unwrap(), Ok(Segment::Integer(98765)));
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct TupleIndex<'a> {
+    input: &'a [u8],
+    /// The start of each element, followed by the end of the input
+    offsets: Vec<usize>,
+}
+
+impl<'a> TupleIndex<'a> {
+    /// Index an encoded tuple using the default `DecodeOptions`
+    pub fn new(input: &'a [u8]) -> Result<TupleIndex<'a>, TupleError> {
+        TupleIndex::with_options(input, &DecodeOptions::default())
+    }
+
+    /// Index an encoded tuple, failing if it exceeds any of the given limits
+    ///
+    /// As nested tuples aren't decoded, the element limit only applies to the top level elements.
+    pub fn with_options(input: &'a [u8], options: &DecodeOptions) -> Result<TupleIndex<'a>, TupleError> {
+        options.check_bytes(input.len())?;
+
+        let mut offsets = vec![0];
+        let mut index = 0;
+        while index < input.len() {
+            options.check_elements(offsets.len(), index)?;
+
+            index = options.find_element_end(input, index)?;
+            offsets.push(index);
+        }
+
+        Ok(TupleIndex { input, offsets })
+    }
+
+    /// The number of top level elements
+    pub fn len(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    /// Whether the tuple has no elements
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The position of the first byte of an element
+    pub fn offset(&self, index: usize) -> Option<usize> {
+        if index < self.len() {
+            Some(self.offsets[index])
+        } else {
+            None
+        }
+    }
+
+    /// The type code of an element
+    pub fn type_code(&self, index: usize) -> Option<u8> {
+        self.offset(index).map(|offset| self.input[offset])
+    }
+
+    /// The encoded bytes of an element
+    pub fn get(&self, index: usize) -> Option<&'a [u8]> {
+        self.slice(index..index.checked_add(1)?)
+    }
+
+    /// The encoded bytes of a range of elements, which form a valid tuple on their own
+    pub fn slice(&self, range: Range<usize>) -> Option<&'a [u8]> {
+        if range.start <= range.end && range.end <= self.len() {
+            Some(&self.input[self.offsets[range.start]..self.offsets[range.end]])
+        } else {
+            None
+        }
+    }
+
+    /// Decode a single element
+    pub fn segment(&self, index: usize) -> Option<Result<Segment, TupleError>> {
+        self.get(index).map(|bytes| Segment::decode(bytes).map(|mut segments| segments.remove(0)))
+    }
+
+    /// The encoded tuple which was indexed
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.input
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::String;
+    use constants::*;
+    use decode::Limit;
+
+    const INPUT: [u8; 12] = [STRING_CODE, 97, NULL, 0xFF, NULL, NESTED_CODE, TRUE_CODE, NULL, INT_ZERO_CODE, INT_ZERO_CODE + 2, 1, 0];
+
+    #[test]
+    fn test_index() {
+        let index = TupleIndex::new(&INPUT[..]).unwrap();
+
+        assert_eq!(index.len(), 4);
+        assert_eq!(index.offset(1), Some(5));
+        assert_eq!(index.offset(4), None);
+        assert_eq!(index.type_code(1), Some(NESTED_CODE));
+        assert_eq!(index.get(0), Some(&INPUT[..5]));
+        assert_eq!(index.get(3), Some(&INPUT[9..]));
+        assert_eq!(index.get(4), None);
+        assert_eq!(index.get(usize::MAX), None);
+        assert_eq!(index.segment(usize::MAX), None);
+    }
+
+    #[test]
+    fn test_slice() {
+        let index = TupleIndex::new(&INPUT[..]).unwrap();
+
+        assert_eq!(index.slice(0..4), Some(&INPUT[..]));
+        assert_eq!(index.slice(1..3), Some(&INPUT[5..9]));
+        assert_eq!(index.slice(2..2), Some(&[][..]));
+        assert_eq!(index.slice(3..5), None);
+        assert_eq!(Segment::decode(index.slice(1..3).unwrap()), Ok(vec![
+            Segment::Nested(vec![Segment::Boolean(true)]),
+            Segment::Integer(0),
+        ]));
+    }
+
+    #[test]
+    fn test_segment() {
+        let index = TupleIndex::new(&INPUT[..]).unwrap();
+
+        assert_eq!(index.segment(0), Some(Ok(Segment::String(String::from("a\0")))));
+        assert_eq!(index.segment(3), Some(Ok(Segment::Integer(256))));
+        assert_eq!(index.segment(4), None);
+    }
+
+    #[test]
+    fn test_empty() {
+        let index = TupleIndex::new(&[]).unwrap();
+
+        assert!(index.is_empty());
+        assert_eq!(index.slice(0..0), Some(&[][..]));
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert_eq!(TupleIndex::new(&[TRUE_CODE, 0x99]).err(), Some(TupleError::DecodeError { position: 1, type_code: 0x99 }));
+        assert_eq!(TupleIndex::new(&[TRUE_CODE, NULL]).err(), Some(TupleError::TruncatedTuple));
+        assert_eq!(TupleIndex::new(&[INT_ZERO_CODE + 2, 1]).err(), Some(TupleError::IntegerDecodeError { position: 0 }));
+    }
+
+    #[test]
+    fn test_limits() {
+        let options = DecodeOptions::new().max_elements(3);
+        assert_eq!(TupleIndex::with_options(&INPUT[..], &options).err(), Some(TupleError::LimitExceeded { limit: Limit::Elements, position: 9 }));
+
+        let options = DecodeOptions::new().max_depth(0);
+        assert_eq!(TupleIndex::with_options(&INPUT[..], &options).err(), Some(TupleError::LimitExceeded { limit: Limit::Depth, position: 5 }));
+    }
+}
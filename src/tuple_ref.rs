@@ -0,0 +1,275 @@
+//! Borrowed views of encoded tuples
+//!
+//! A `TupleRef` offers the read side of the `Tuple` API over a borrowed slice, so keys returned by
+//! a store can be decoded and compared without copying them first.
+
+use alloc::vec::Vec;
+use core::fmt;
+use decode::{DecodeOptions, DecodeState, Unknown};
+use errors::TupleError;
+use prefix::common_prefix_len;
+use segment::Segment;
+use tuple_index::TupleIndex;
+use {descending, printable, text, Tuple};
+
+/// A borrowed encoded tuple
+///
+/// # Examples
+/// ```
+/// #[macro_use] extern crate binary_tuples;
+///
+/// use binary_tuples::{TupleRef, segment::Segment};
+///
+/// # fn main() {
+/// let key: &[u8] = &[2, 117, 115, 101, 114, 115, 0, 21, 1];
+/// let tuple = TupleRef::new(key);
+///
+/// assert!(tuple.starts_with(tuple!("users").as_tuple_ref()));
+/// assert_eq!(tuple.iter().nth(1).unwrap(), Ok(Segment::Integer(1)));
+/// assert_eq!(tuple.to_owned(), tuple!("users", 1));
+/// # }
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TupleRef<'a> {
+    buffer: &'a [u8],
+}
+
+impl<'a> TupleRef<'a> {
+    /// Wrap an encoded tuple
+    pub fn new(buffer: &'a [u8]) -> TupleRef<'a> {
+        TupleRef { buffer }
+    }
+
+    /// Return the encoded tuple
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.buffer
+    }
+
+    /// The number of bytes in the encoded tuple
+    pub fn encoded_len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Copy the encoded tuple into a new `Tuple`
+    pub fn to_owned(&self) -> Tuple {
+        Tuple::from_bytes(self.buffer)
+    }
+
+    /// Deserialize the segments which make up this tuple, using the default `DecodeOptions`
+    pub fn as_segments(&self) -> Result<Vec<Segment>, TupleError> {
+        Segment::decode(self.buffer)
+    }
+
+    /// Deserialize the segments which make up this tuple, failing if any of the given limits are
+    /// exceeded
+    pub fn as_segments_with(&self, options: &DecodeOptions) -> Result<Vec<Segment>, TupleError> {
+        options.decode(self.buffer)
+    }
+
+    /// Deserialize as many segments as possible, returning any bytes which couldn't be decoded (see
+    /// `DecodeOptions::decode_lenient`)
+    pub fn as_segments_lenient(&self) -> (Vec<Segment>, Option<Unknown<'a>>) {
+        DecodeOptions::default().decode_lenient(self.buffer)
+    }
+
+    /// Iterate over the decoded elements of this tuple, using the default `DecodeOptions`
+    pub fn iter(&self) -> Iter<'a> {
+        self.iter_with(DecodeOptions::default())
+    }
+
+    /// Iterate over the decoded elements of this tuple, failing if any of the given limits are
+    /// exceeded
+    pub fn iter_with(&self, options: DecodeOptions) -> Iter<'a> {
+        Iter {
+            input: self.buffer,
+            position: 0,
+            elements: 0,
+            options,
+        }
+    }
+
+    /// Index the top level elements of this tuple for random access (see `TupleIndex`)
+    pub fn index(&self) -> Result<TupleIndex<'a>, TupleError> {
+        TupleIndex::new(self.buffer)
+    }
+
+    /// Check that this tuple is canonically encoded (see `Tuple::validate_canonical`)
+    pub fn validate_canonical(&self) -> Result<(), TupleError> {
+        DecodeOptions::new().strict(true).decode(self.buffer).map(|_| ())
+    }
+
+    /// Whether the elements of `prefix` are the first elements of this tuple
+    pub fn starts_with(&self, prefix: TupleRef) -> bool {
+        common_prefix_len(self.buffer, prefix.buffer) == prefix.buffer.len()
+    }
+
+    /// Remove `prefix` from the start of this tuple, returning the remaining elements or `None` if
+    /// this tuple doesn't start with `prefix`
+    pub fn strip_prefix(&self, prefix: TupleRef) -> Option<TupleRef<'a>> {
+        if self.starts_with(prefix) {
+            Some(TupleRef::new(&self.buffer[prefix.buffer.len()..]))
+        } else {
+            None
+        }
+    }
+
+    /// The longest run of elements that both tuples start with
+    pub fn common_prefix(a: TupleRef<'a>, b: TupleRef) -> TupleRef<'a> {
+        TupleRef::new(&a.buffer[..common_prefix_len(a.buffer, b.buffer)])
+    }
+
+    /// Return the range of keys which contain this tuple as a prefix (see `Tuple::range`)
+    pub fn range(&self) -> (Vec<u8>, Vec<u8>) {
+        let mut begin = Vec::with_capacity(self.buffer.len() + 1);
+        begin.extend_from_slice(self.buffer);
+        begin.push(0x00);
+
+        let mut end = Vec::with_capacity(self.buffer.len() + 1);
+        end.extend_from_slice(self.buffer);
+        end.push(0xFF);
+
+        (begin, end)
+    }
+
+    /// Format the tuple in its textual representation, or as escaped bytes if it is invalid
+    pub(crate) fn fmt_named(&self, f: &mut fmt::Formatter, name: &str) -> fmt::Result {
+        match descending::decode(self.buffer) {
+            Ok(segments) => write!(f, "{}{}", name, text::format(&segments)),
+            Err(_) => write!(f, "{}(<invalid: {}>)", name, printable::printable(self.buffer)),
+        }
+    }
+}
+
+impl<'a> fmt::Debug for TupleRef<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_named(f, "TupleRef")
+    }
+}
+
+impl<'a> From<&'a [u8]> for TupleRef<'a> {
+    fn from(buffer: &'a [u8]) -> TupleRef<'a> {
+        TupleRef { buffer }
+    }
+}
+
+impl<'a> From<&'a Tuple> for TupleRef<'a> {
+    fn from(tuple: &'a Tuple) -> TupleRef<'a> {
+        tuple.as_tuple_ref()
+    }
+}
+
+impl<'a> From<TupleRef<'a>> for Tuple {
+    fn from(tuple: TupleRef<'a>) -> Tuple {
+        tuple.to_owned()
+    }
+}
+
+impl<'a> AsRef<[u8]> for TupleRef<'a> {
+    fn as_ref(&self) -> &[u8] {
+        self.buffer
+    }
+}
+
+impl<'a> IntoIterator for TupleRef<'a> {
+    type Item = Result<Segment, TupleError>;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+/// An iterator over the decoded elements of a tuple
+///
+/// Elements are decoded as they are reached. After an element fails to decode its error is
+/// returned and iteration stops.
+pub struct Iter<'a> {
+    input: &'a [u8],
+    position: usize,
+    elements: usize,
+    options: DecodeOptions,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = Result<Segment, TupleError>;
+
+    fn next(&mut self) -> Option<Result<Segment, TupleError>> {
+        if self.position >= self.input.len() {
+            return None;
+        }
+
+        let mut state = DecodeState { options: &self.options, elements: self.elements };
+        match self.options.decode_element(self.input, self.position, &mut state) {
+            Ok((mut segments, end)) => {
+                self.position = end;
+                self.elements = state.elements;
+                Some(Ok(segments.remove(0)))
+            }
+            Err(err) => {
+                self.position = self.input.len();
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::String;
+    use constants::*;
+    use decode::Limit;
+
+    #[test]
+    fn test_iter() {
+        let tuple = ::tuple!("users", 1, vec![0]);
+        let segments = tuple.as_tuple_ref().iter().collect::<Result<Vec<_>, _>>().unwrap();
+
+        assert_eq!(segments, tuple.as_segments().unwrap());
+        assert_eq!(TupleRef::new(&[]).iter().next(), None);
+    }
+
+    #[test]
+    fn test_iter_errors() {
+        let mut iter = TupleRef::new(&[TRUE_CODE, 0x99, FALSE_CODE]).iter();
+
+        assert_eq!(iter.next(), Some(Ok(Segment::Boolean(true))));
+        assert_eq!(iter.next(), Some(Err(TupleError::DecodeError { position: 1, type_code: 0x99 })));
+        assert_eq!(iter.next(), None);
+
+        let mut iter = TupleRef::new(&[TRUE_CODE, FALSE_CODE]).iter_with(DecodeOptions::new().max_elements(1));
+
+        assert_eq!(iter.next(), Some(Ok(Segment::Boolean(true))));
+        assert_eq!(iter.next(), Some(Err(TupleError::LimitExceeded { limit: Limit::Elements, position: 1 })));
+    }
+
+    #[test]
+    fn test_prefixes() {
+        let tuple = ::tuple!("users", 1, "posts");
+        let key = TupleRef::new(tuple.as_bytes());
+        let prefix = ::tuple!("users", 1);
+
+        assert!(key.starts_with(prefix.as_tuple_ref()));
+        assert!(!key.starts_with(::tuple!("user").as_tuple_ref()));
+        assert_eq!(key.strip_prefix(prefix.as_tuple_ref()).unwrap().as_bytes(), ::tuple!("posts").as_bytes());
+        assert_eq!(TupleRef::common_prefix(key, ::tuple!("users", 2).as_tuple_ref()).as_bytes(), ::tuple!("users").as_bytes());
+    }
+
+    #[test]
+    fn test_decode() {
+        let tuple = ::tuple!("users", 1);
+        let key = TupleRef::from(tuple.as_bytes());
+
+        assert_eq!(key.as_segments().unwrap(), vec![Segment::String(String::from("users")), Segment::Integer(1)]);
+        assert_eq!(key.index().unwrap().len(), 2);
+        assert_eq!(key.range(), tuple.range());
+        assert!(key.validate_canonical().is_ok());
+        assert_eq!(Tuple::from(key), tuple);
+    }
+
+    #[test]
+    fn test_debug() {
+        assert_eq!(format!("{:?}", TupleRef::new(&[INT_ZERO_CODE + 1, 1])), "TupleRef(1)");
+        assert_eq!(format!("{:?}", TupleRef::new(&[0x99])), "TupleRef(<invalid: \\x99>)");
+    }
+}
@@ -1,47 +1,203 @@
+use alloc::vec::Vec;
+use memchr::{memchr, memchr_iter};
 use constants::*;
 
-pub fn decode_byte_string(input: &[u8]) -> (usize, Vec<u8>) {
-    let mut read = 0;
-    let mut skip = false;
-    let mut bytes = Vec::with_capacity(1024);
-    for vals in input.windows(2) {
-        if skip {
-            skip = false;
-            read += 1;
-        } else {
-            if vals[0] == 0 {
-                read += 1;
-                if vals[1] != 0xFF {
-                    return (read, bytes)
-                } else {
-                    bytes.push(vals[0]);
-                    skip = true;
-                }
-            } else {
-                read += 1;
-                bytes.push(vals[0])
+/// Strings with more than one null in every `DENSE_NULLS` bytes are escaped and unescaped one byte at
+/// a time, as the runs between nulls are too short for bulk copies to pay off
+const DENSE_NULLS: usize = 16;
+
+/// Decode an escaped byte string, returning the number of bytes read (including the terminator)
+/// along with the unescaped bytes, or `None` if the terminator is missing
+///
+/// The input is scanned twice - first to find the terminator and the number of escaped nulls so
+/// that the output can be allocated with its exact size, then to copy the runs of bytes between
+/// the escaped nulls. Both passes use `memchr` to skip over bytes which aren't null. Once nulls
+/// turn out to be dense the rest of the string is decoded by `decode_dense_byte_string` instead.
+pub fn decode_byte_string(input: &[u8]) -> Option<(usize, Vec<u8>)> {
+    let mut position = 0;
+    let mut escapes = 0;
+    let end = loop {
+        if escapes * DENSE_NULLS > position {
+            return decode_dense_byte_string(input);
+        }
+
+        match memchr(NULL, &input[position..]) {
+            Some(offset) if input.get(position + offset + 1) == Some(&NULL_ESCAPE) => {
+                escapes += 1;
+                position += offset + 2;
+            }
+            Some(offset) => break position + offset,
+            None => return None
+        }
+    };
+
+    let mut bytes = Vec::with_capacity(end - escapes);
+    let mut start = 0;
+    while let Some(offset) = memchr(NULL, &input[start..end]) {
+        bytes.extend_from_slice(&input[start..=start + offset]);
+        start += offset + 2;
+    }
+    bytes.extend_from_slice(&input[start..end]);
+
+    Some((end + 1, bytes))
+}
+
+/// Decode a byte string containing many nulls in a single pass
+///
+/// The output is allocated with enough capacity for the rest of the input, as finding its exact
+/// size would need another pass.
+fn decode_dense_byte_string(input: &[u8]) -> Option<(usize, Vec<u8>)> {
+    let mut bytes = Vec::with_capacity(input.len());
+
+    let mut index = 0;
+    while index < input.len() {
+        let value = input[index];
+        if value == NULL {
+            if input.get(index + 1) != Some(&NULL_ESCAPE) {
+                return Some((index + 1, bytes));
             }
+            index += 2;
+        } else {
+            index += 1;
+        }
+
+        bytes.push(value);
+    }
+
+    None
+}
+
+/// Find the end of an escaped byte string whose bytes have been XORed with `mask` (which is `0xFF`
+/// for descending elements), returning the position following its terminator
+pub fn byte_string_end(input: &[u8], mask: u8) -> Option<usize> {
+    let mut position = 0;
+    loop {
+        let offset = memchr(NULL ^ mask, &input[position..])?;
+        if input.get(position + offset + 1) == Some(&(NULL_ESCAPE ^ mask)) {
+            position += offset + 2;
+        } else {
+            return Some(position + offset + 1);
         }
     }
+}
+
+/// The number of bytes following the type code of a fixed length element, or `None` for byte
+/// strings, nested tuples and unknown type codes
+pub fn fixed_len(type_code: u8) -> Option<usize> {
+    match type_code {
+        INT_NEG_MIN_CODE..=INT_NEG_MAX_CODE => Some((INT_ZERO_CODE - type_code) as usize),
+        INT_POS_MIN_CODE..=INT_POS_MAX_CODE => Some((type_code - INT_ZERO_CODE) as usize),
+        INT_ZERO_CODE | TRUE_CODE | FALSE_CODE => Some(0),
+        FLOAT_CODE => Some(4),
+        DOUBLE_CODE => Some(8),
+        UUID_CODE => Some(16),
+        _ => None
+    }
+}
+
+/// A destination for encoded bytes
+pub trait Output {
+    fn push(&mut self, value: u8);
+
+    fn extend_from_slice(&mut self, values: &[u8]);
+
+    fn reserve(&mut self, _additional: usize) {}
+}
+
+impl Output for Vec<u8> {
+    fn push(&mut self, value: u8) {
+        Vec::push(self, value)
+    }
+
+    fn extend_from_slice(&mut self, values: &[u8]) {
+        Vec::extend_from_slice(self, values)
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        Vec::reserve(self, additional)
+    }
+}
+
+/// Writes into a fixed size slice - callers are expected to check the required length up front
+pub struct SliceOutput<'a> {
+    buffer: &'a mut [u8],
+    position: usize,
+}
+
+impl<'a> SliceOutput<'a> {
+    pub fn new(buffer: &'a mut [u8]) -> SliceOutput<'a> {
+        SliceOutput { buffer, position: 0 }
+    }
 
-    (read + 1, bytes)
+    pub fn position(&self) -> usize {
+        self.position
+    }
+}
+
+impl<'a> Output for SliceOutput<'a> {
+    fn push(&mut self, value: u8) {
+        self.buffer[self.position] = value;
+        self.position += 1;
+    }
+
+    fn extend_from_slice(&mut self, values: &[u8]) {
+        self.buffer[self.position..self.position + values.len()].copy_from_slice(values);
+        self.position += values.len();
+    }
+}
+
+#[cfg(feature = "bytes")]
+pub struct BufOutput<'a, B: 'a>(pub &'a mut B);
+
+#[cfg(feature = "bytes")]
+impl<'a, B: ::bytes::BufMut> Output for BufOutput<'a, B> {
+    fn push(&mut self, value: u8) {
+        self.0.put_u8(value)
+    }
+
+    fn extend_from_slice(&mut self, values: &[u8]) {
+        self.0.put_slice(values)
+    }
+}
+
+/// The number of bytes required to encode a byte string, including the type code and terminator
+pub fn escaped_len(input: &[u8]) -> usize {
+    input.len() + 2 + count_nulls(input)
+}
+
+/// Count the nulls in the input, in chunks small enough for a byte sized counter so that the
+/// comparisons can be vectorised
+fn count_nulls(input: &[u8]) -> usize {
+    input.chunks(255)
+        .map(|chunk| chunk.iter().fold(0u8, |count, value| count + (*value == NULL) as u8) as usize)
+        .sum()
 }
 
-pub fn encode_byte_string(type_code: u8, input: &[u8], buffer: &mut Vec<u8>) {
-    buffer.reserve(input.len() + 2);
+/// Encode a byte string, escaping any nulls
+///
+/// Runs of bytes between nulls are copied to the output in bulk rather than byte by byte, unless
+/// the nulls are dense.
+pub fn encode_byte_string<O: Output>(type_code: u8, input: &[u8], buffer: &mut O) {
+    let nulls = count_nulls(input);
+    buffer.reserve(input.len() + 2 + nulls);
 
     buffer.push(type_code);
 
-    for i in 0..input.len() {
-        match input[i] {
-            NULL => {
-                buffer.push(NULL);
+    if nulls * DENSE_NULLS > input.len() {
+        for value in input.iter() {
+            buffer.push(*value);
+            if *value == NULL {
                 buffer.push(NULL_ESCAPE);
             }
-            value => {
-                buffer.push(value)
-            }
         }
+    } else {
+        let mut start = 0;
+        for index in memchr_iter(NULL, input) {
+            buffer.extend_from_slice(&input[start..=index]);
+            buffer.push(NULL_ESCAPE);
+            start = index + 1;
+        }
+        buffer.extend_from_slice(&input[start..]);
     }
 
     buffer.push(NULL);
@@ -49,8 +205,8 @@ pub fn encode_byte_string(type_code: u8, input: &[u8], buffer: &mut Vec<u8>) {
 
 pub fn encode_sortable_float(bytes: &mut [u8]) {
     if (bytes[0] & 0x80) != 0x00 {
-        for i in 0..bytes.len() {
-            bytes[i] ^= 0xff;
+        for byte in bytes.iter_mut() {
+            *byte ^= 0xff;
         }
     } else {
         bytes[0] ^= 0x80;
@@ -59,10 +215,72 @@ pub fn encode_sortable_float(bytes: &mut [u8]) {
 
 pub fn decode_sortable_float(bytes: &mut [u8]) {
     if (bytes[0] & 0x80) != 0x80 {
-        for i in 0..bytes.len() {
-            bytes[i] ^= 0xff;
+        for byte in bytes.iter_mut() {
+            *byte ^= 0xff;
         }
     } else {
         bytes[0] ^= 0x80;
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(input: &[u8]) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        encode_byte_string(BYTES_CODE, input, &mut buffer);
+        buffer
+    }
+
+    #[test]
+    fn test_encode_runs() {
+        assert_eq!(encode(&[]), vec![BYTES_CODE, NULL]);
+        assert_eq!(encode(&[0]), vec![BYTES_CODE, 0, 255, NULL]);
+        assert_eq!(encode(&[0, 0, 1, 2, 0, 3]), vec![BYTES_CODE, 0, 255, 0, 255, 1, 2, 0, 255, 3, NULL]);
+    }
+
+    #[test]
+    fn test_decode_runs() {
+        assert_eq!(decode_byte_string(&[0, 255, 0, 255, 1, 2, 0, 255, 3, 0, 9]), Some((10, vec![0, 0, 1, 2, 0, 3])));
+        assert_eq!(decode_byte_string(&[0]), Some((1, vec![])));
+    }
+
+    #[test]
+    fn test_decode_dense() {
+        let input = [0, 255, 0, 255, 0, 255, 1, 0, 255, 0, 7];
+
+        assert_eq!(decode_byte_string(&input), decode_dense_byte_string(&input));
+        assert_eq!(decode_dense_byte_string(&input), Some((10, vec![0, 0, 0, 1, 0])));
+        assert_eq!(decode_dense_byte_string(&[0, 255, 1, 0]), Some((4, vec![0, 1])));
+        assert_eq!(decode_dense_byte_string(&[0, 255, 1]), None);
+    }
+
+    #[test]
+    fn test_decode_unterminated() {
+        assert_eq!(decode_byte_string(&[1, 2, 3]), None);
+        assert_eq!(decode_byte_string(&[1, 0, 255]), None);
+        assert_eq!(decode_byte_string(&[0, 255, 0, 255, 0, 255]), None);
+        assert_eq!(decode_byte_string(&[]), None);
+    }
+
+    #[test]
+    fn test_byte_string_end() {
+        assert_eq!(byte_string_end(&[1, 0, 21], 0), Some(2));
+        assert_eq!(byte_string_end(&[0, 255, 0, 255, 0], 0), Some(5));
+        assert_eq!(byte_string_end(&[1, 0, 255], 0), None);
+        assert_eq!(byte_string_end(&[], 0), None);
+        assert_eq!(byte_string_end(&[254, 255, 0, 255, 234], 0xFF), Some(4));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for spacing in [2, 7, 100].iter() {
+            let input = (0..4096).map(|i| if i % spacing == 0 { 0 } else { i as u8 | 1 }).collect::<Vec<u8>>();
+            let encoded = encode(&input);
+
+            assert_eq!(escaped_len(&input), encoded.len());
+            assert_eq!(decode_byte_string(&encoded[1..]), Some((encoded.len() - 1, input)));
+        }
+    }
+}